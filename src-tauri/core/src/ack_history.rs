@@ -0,0 +1,165 @@
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::build_version::BuildVersion;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AckAction {
+    Acknowledged,
+    Undone
+}
+
+/// A single entry in the acknowledgement audit log.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AckRecord {
+    pub action: AckAction,
+    pub version: BuildVersion,
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+    pub user: Option<String>,
+    pub note: Option<String>
+}
+
+impl AckRecord {
+    pub fn new(action: AckAction, version: BuildVersion, source: &str, note: Option<String>) -> Self {
+        Self {
+            action,
+            version,
+            source: source.into(),
+            timestamp: Utc::now(),
+            user: env::var("USER").or(env::var("USERNAME")).ok(),
+            note
+        }
+    }
+}
+
+/// Append-only acknowledgement history, one JSON record per line.
+pub struct AckHistory {
+    path: PathBuf
+}
+
+impl AckHistory {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn append(&self, record: &AckRecord) -> Result<(), anyhow::Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        // Start on a fresh line if a previous write was torn.
+        let mut line = String::new();
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.push('\n');
+            }
+        }
+        line.push_str(&serde_json::to_string(record)?);
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// All records, oldest first. Lines which can't be parsed (e.g. a torn last write) are skipped.
+    pub fn records(&self) -> Vec<AckRecord> {
+        let s = match fs::read_to_string(&self.path) {
+            Ok(s) => s,
+            Err(_) => return Vec::new()
+        };
+
+        s.lines()
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|l| match serde_json::from_str(l) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    warn!("Skipping unreadable ack history entry in {}. Error: {}", self.path.display(), e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn for_source(&self, source: &str) -> Vec<AckRecord> {
+        self.records().into_iter().filter(|r| r.source == source).collect()
+    }
+
+    pub fn since(&self, timestamp: DateTime<Utc>) -> Vec<AckRecord> {
+        self.records().into_iter().filter(|r| r.timestamp >= timestamp).collect()
+    }
+
+    /// Replays the log for a source, returning the acknowledged versions still in effect, oldest first.
+    pub fn effective(&self, source: &str) -> Vec<BuildVersion> {
+        effective(&self.for_source(source))
+    }
+}
+
+/// Replays `records`, returning the acknowledged versions still in effect, oldest first.
+pub fn effective(records: &[AckRecord]) -> Vec<BuildVersion> {
+    let mut stack = Vec::new();
+    for r in records {
+        match r.action {
+            AckAction::Acknowledged => stack.push(r.version),
+            AckAction::Undone => {
+                stack.pop();
+            }
+        }
+    }
+    stack
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use tempfile::tempdir;
+    use crate::ack_history::{AckAction, AckHistory, AckRecord};
+    use crate::build_version::BuildVersion;
+
+    fn version(t: i32) -> BuildVersion {
+        BuildVersion { major: 1, minor: 2, patch: 3, t }
+    }
+
+    #[test]
+    fn replays_acknowledgements_and_undos_per_source() {
+        let dir = tempdir().unwrap();
+        let history = AckHistory::new(&dir.path().join("ack_history.jsonl"));
+
+        history.append(&AckRecord::new(AckAction::Acknowledged, version(1), "a", None)).unwrap();
+        history.append(&AckRecord::new(AckAction::Acknowledged, version(2), "a", Some("installed".into()))).unwrap();
+        history.append(&AckRecord::new(AckAction::Acknowledged, version(9), "b", None)).unwrap();
+        history.append(&AckRecord::new(AckAction::Undone, version(2), "a", None)).unwrap();
+
+        assert_eq!(history.records().len(), 4);
+        assert_eq!(history.for_source("a")[1].note.as_deref(), Some("installed"));
+        assert_eq!(history.effective("a"), vec![version(1)]);
+        assert_eq!(history.effective("b"), vec![version(9)]);
+    }
+
+    #[test]
+    fn skips_torn_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ack_history.jsonl");
+        let history = AckHistory::new(&path);
+
+        history.append(&AckRecord::new(AckAction::Acknowledged, version(1), "a", None)).unwrap();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"action\":\"ackno").unwrap();
+
+        assert_eq!(history.effective("a"), vec![version(1)]);
+
+        history.append(&AckRecord::new(AckAction::Acknowledged, version(2), "a", None)).unwrap();
+        assert_eq!(history.effective("a"), vec![version(1), version(2)]);
+    }
+}
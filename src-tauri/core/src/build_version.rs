@@ -1,8 +1,9 @@
 use std::fmt::{Display, Formatter};
-use regex::{Captures, Regex};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tracing::error;
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct BuildVersion {
     pub major: i32,
     pub minor: i32,
@@ -40,4 +41,17 @@ impl Display for BuildVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "R{}.{:0>2}.{:0>2}T{:0>2}", self.major, self.minor, self.patch, self.t)
     }
-}
\ No newline at end of file
+}
+
+impl Serialize for BuildVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BuildVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        BuildVersion::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
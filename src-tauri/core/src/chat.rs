@@ -0,0 +1,291 @@
+//! Posts new versions to team channels through Slack, Microsoft Teams or Mattermost incoming
+//! webhooks.
+//!
+//! Every instance of the app watching a share sees the same new version, so before posting each
+//! one tries to claim the version with a marker file in `dedup_dir`. Only the instance that
+//! creates the marker posts. When the marker can't be written at all the message is posted
+//! anyway; a duplicate is better than no announcement.
+
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use crate::notifier::Notifier;
+use crate::release::DetectedRelease;
+use crate::template;
+use crate::webhook::{WebhookConfig, WebhookNotifier};
+
+/// Where instances record what they posted unless `dedup_dir` says otherwise, inside the watched
+/// folder so every instance watching it sees the same markers.
+pub const DEDUP_DIR: &str = ".release-monitor-posted";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatKind {
+    Slack,
+    Teams,
+    Mattermost
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_dedup() -> bool {
+    true
+}
+
+/// A channel to announce new versions in.
+///
+/// ```toml
+/// [[chats]]
+/// kind = "slack"
+/// url = "https://hooks.slack.com/services/..."
+/// link = "https://builds.example.com/{version}"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChatConfig {
+    pub kind: ChatKind,
+    pub url: String,
+    /// Where the message links to, with `{version}`, `{source}` and `{path}` placeholders.
+    /// Defaults to the build's path.
+    #[serde(default)]
+    pub link: Option<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Only the first instance to see a version posts it.
+    #[serde(default = "default_dedup")]
+    pub dedup: bool,
+    /// Where instances record what they posted. Every instance needs to point at the same
+    /// writable folder. Defaults to [`DEDUP_DIR`] in the watched folder.
+    #[serde(default)]
+    pub dedup_dir: Option<String>
+}
+
+/// Builds the kind's message payload.
+pub fn payload(kind: ChatKind, release: &DetectedRelease, link: Option<&str>) -> Value {
+    let title = format!("New build {} on {}", release.version, release.source);
+    match kind {
+        ChatKind::Slack => {
+            let mut text = format!("*New build {}* on *{}*", release.version, release.source);
+            if let Some(link) = link {
+                text.push_str(&format!("\n<{}|Open>", link));
+            }
+            json!({
+                "text": title,
+                "blocks": [
+                    { "type": "section", "text": { "type": "mrkdwn", "text": text } },
+                    { "type": "context", "elements": [{ "type": "mrkdwn", "text": format!("Version {} · Source {}", release.version, release.source) }] }
+                ]
+            })
+        }
+        ChatKind::Mattermost => json!({
+            "username": "Release Monitor",
+            "text": title,
+            "attachments": [{
+                "fallback": title,
+                "title": title,
+                "title_link": link,
+                "fields": [
+                    { "short": true, "title": "Version", "value": release.version.to_string() },
+                    { "short": true, "title": "Source", "value": release.source }
+                ]
+            }]
+        }),
+        ChatKind::Teams => {
+            let actions: Vec<Value> = link.iter()
+                .map(|l| json!({ "@type": "OpenUri", "name": "Open", "targets": [{ "os": "default", "uri": l }] }))
+                .collect();
+            json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": title,
+                "themeColor": "0076D7",
+                "title": title,
+                "sections": [{
+                    "facts": [
+                        { "name": "Version", "value": release.version.to_string() },
+                        { "name": "Source", "value": release.source }
+                    ]
+                }],
+                "potentialAction": actions
+            })
+        }
+    }
+}
+
+pub struct ChatNotifier {
+    config: ChatConfig,
+    webhook: WebhookNotifier,
+    name: String
+}
+
+impl ChatNotifier {
+    pub fn new(config: ChatConfig) -> Self {
+        let webhook = WebhookNotifier::new(WebhookConfig {
+            url: config.url.clone(),
+            method: String::from("POST"),
+            body: None,
+            secret: None,
+            retries: config.retries
+        });
+        let name = format!("{:?} message", config.kind);
+        Self { config, webhook, name }
+    }
+
+    pub fn with_webhook(mut self, webhook: WebhookNotifier) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
+    /// Sets where posts are recorded unless the config already says.
+    pub fn with_dedup_dir(mut self, dir: &Path) -> Self {
+        if self.config.dedup_dir.is_none() {
+            self.config.dedup_dir = Some(dir.to_string_lossy().to_string());
+        }
+        self
+    }
+
+    /// The marker claiming `release` for this channel, keyed by the channel so several teams can
+    /// announce the same build.
+    fn marker(&self, release: &DetectedRelease) -> Option<PathBuf> {
+        let dir = Path::new(self.config.dedup_dir.as_deref()?);
+        let channel = hex::encode(Sha256::digest(self.config.url.as_bytes()));
+        Some(dir.join(format!("{}-{}-{}.posted", release.source, release.version, &channel[..12])))
+    }
+
+    /// Creates the marker. `false` if another instance already did.
+    fn claim(marker: &Path) -> Result<bool, std::io::Error> {
+        if let Some(dir) = marker.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        match OpenOptions::new().write(true).create_new(true).open(marker) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e)
+        }
+    }
+}
+
+impl Notifier for ChatNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error> {
+        let mut marker = if self.config.dedup { self.marker(release) } else { None };
+        if let Some(m) = &marker {
+            match Self::claim(m) {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("{} for {} was already posted by another instance.", self.name, release.version);
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("Unable to record {} for {} in {}, posting anyway. Error: {}", self.name, release.version, m.display(), e);
+                    marker = None;
+                }
+            }
+        }
+
+        let link = match &self.config.link {
+            Some(t) => Some(template::render(t, &release.vars())),
+            None => release.path.clone()
+        };
+        let result = self.webhook.deliver(&payload(self.config.kind, release, link.as_deref()).to_string());
+
+        // Let another instance try if this one couldn't get through.
+        if let (Err(_), Some(marker)) = (&result, &marker) {
+            let _ = fs::remove_file(marker);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+    use serde_json::Value;
+    use crate::build_version::BuildVersion;
+    use crate::chat::{payload, ChatConfig, ChatKind, ChatNotifier};
+    use crate::health::Backoff;
+    use crate::notifier::Notifier;
+    use crate::release::DetectedRelease;
+    use crate::testing::http_receiver;
+    use crate::webhook::{WebhookConfig, WebhookNotifier};
+
+    fn release(path: Option<String>) -> DetectedRelease {
+        DetectedRelease { source: String::from("a"), version: BuildVersion::parse("R1.02.03T04").unwrap(), path, ..Default::default() }
+    }
+
+    fn chat(url: &str, retries: u32, dedup_dir: &Path) -> ChatNotifier {
+        let config = ChatConfig {
+            kind: ChatKind::Slack,
+            url: url.into(),
+            link: Some(String::from("https://builds/{version}")),
+            retries,
+            dedup: true,
+            dedup_dir: Some(dedup_dir.to_string_lossy().to_string())
+        };
+        let webhook = WebhookNotifier::new(WebhookConfig { url: url.into(), method: String::from("POST"), body: None, secret: None, retries })
+            .with_backoff(Backoff { initial: Duration::from_millis(10), max: Duration::from_millis(10) });
+        ChatNotifier::new(config).with_webhook(webhook)
+    }
+
+    #[test]
+    fn builds_each_kinds_card() {
+        let release = release(None);
+        let slack = payload(ChatKind::Slack, &release, Some("https://builds/1"));
+        assert!(slack["blocks"][0]["text"]["text"].as_str().unwrap().ends_with("<https://builds/1|Open>"));
+
+        let mattermost = payload(ChatKind::Mattermost, &release, None);
+        assert_eq!(mattermost["attachments"][0]["fields"][0]["value"], "R1.02.03T04");
+        assert_eq!(mattermost["attachments"][0]["title_link"], Value::Null);
+
+        let teams = payload(ChatKind::Teams, &release, Some("https://builds/1"));
+        assert_eq!(teams["@type"], "MessageCard");
+        assert_eq!(teams["sections"][0]["facts"][1]["value"], "a");
+        assert_eq!(teams["potentialAction"][0]["targets"][0]["uri"], "https://builds/1");
+    }
+
+    #[test]
+    fn only_the_first_instance_posts() {
+        let share = tempfile::tempdir().unwrap();
+        let path = share.path().join("R1.02.03T04.zip").to_string_lossy().to_string();
+        let dedup = tempfile::tempdir().unwrap();
+
+        let (url, rx) = http_receiver(vec![500, 200]);
+        assert!(chat(&url, 0, dedup.path()).notify(&release(Some(path.clone()))).is_err());
+        chat(&url, 0, dedup.path()).notify(&release(Some(path.clone()))).unwrap();
+        chat(&url, 0, dedup.path()).notify(&release(Some(path))).unwrap();
+
+        let posted: Vec<Value> = rx.iter().map(|r| serde_json::from_str(&r.body).unwrap()).collect();
+        assert_eq!(posted.len(), 2);
+        assert_eq!(posted[1]["text"], "New build R1.02.03T04 on a");
+        assert!(posted[1]["blocks"][0]["text"]["text"].as_str().unwrap().contains("<https://builds/R1.02.03T04|Open>"));
+        // Nothing is left on the share.
+        assert_eq!(fs::read_dir(share.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn posts_anyway_when_the_dedup_folder_is_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dedup = tempfile::tempdir().unwrap();
+        fs::set_permissions(dedup.path(), fs::Permissions::from_mode(0o555)).unwrap();
+        // A file in the way can't be created as a folder, whoever runs the test.
+        let blocked = tempfile::NamedTempFile::new().unwrap();
+
+        let (url, rx) = http_receiver(vec![200, 200]);
+        chat(&url, 0, dedup.path()).notify(&release(None)).unwrap();
+        chat(&url, 0, &blocked.path().join("posted")).notify(&release(None)).unwrap();
+        assert_eq!(rx.iter().count(), 2);
+        fs::set_permissions(dedup.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
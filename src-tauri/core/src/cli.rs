@@ -0,0 +1,255 @@
+use std::io::Write;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use tracing::{error, info};
+use crate::build_version::BuildVersion;
+#[cfg(unix)]
+use crate::daemon;
+use crate::publisher::{Event, Message};
+use crate::services::AppServices;
+use crate::version_checker::UnreachableSourceError;
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+/// `check` found a version that hasn't been acknowledged.
+pub const EXIT_UNACKNOWLEDGED: i32 = 2;
+pub const EXIT_UNREACHABLE: i32 = 3;
+
+/// Without a subcommand the GUI is started.
+#[derive(Debug, Parser)]
+#[command(name = "release-monitor", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Checks the source once. Exits with 2 if the latest version isn't acknowledged.
+    Check,
+    /// Acknowledges a version.
+    Ack {
+        version: String,
+        #[arg(long)]
+        note: Option<String>
+    },
+    /// Undoes the last acknowledgement.
+    Reset,
+    /// Keeps monitoring the source and prints every event, one JSON object per line.
+    Watch {
+        /// Accepted for scripts which ask for JSON explicitly. Output is always JSON.
+        #[arg(long)]
+        json: bool
+    },
+    /// Keeps monitoring without a window and accepts JSON-RPC requests on a Unix socket.
+    #[cfg(unix)]
+    Daemon {
+        /// Defaults to `release-monitor.sock` in `$XDG_RUNTIME_DIR` or the temp dir.
+        #[arg(long)]
+        socket: Option<PathBuf>
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CheckReport {
+    source: String,
+    latest: BuildVersion,
+    acknowledged: BuildVersion,
+    up_to_date: bool
+}
+
+#[derive(Debug, Serialize)]
+struct AckReport {
+    source: String,
+    acknowledged: BuildVersion
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorReport {
+    source: String,
+    error: String
+}
+
+fn print_json<T: Serialize>(out: &mut dyn Write, value: &T) {
+    match serde_json::to_string(value) {
+        Ok(s) => {
+            let _ = writeln!(out, "{}", s);
+        }
+        Err(e) => {
+            error!("Unable to serialize output! Error: {}", e);
+        }
+    }
+}
+
+/// Prints every event until `limit` events were printed, or for as long as the monitor runs.
+fn watch(services: &AppServices, out: &mut dyn Write, limit: Option<usize>) -> i32 {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let _handle = services.release_monitor.subscribe_many(&Event::ALL, move |m: &Message| {
+        let _ = tx.lock().unwrap().send(m.clone());
+    });
+
+    let _ = services.release_monitor.scan_once(Utc::now());
+    match services.release_monitor.start() {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Failed to start monitor! Error: {}", e);
+            return EXIT_ERROR;
+        }
+    }
+
+    for (i, message) in rx.iter().enumerate() {
+        print_json(out, &message);
+        let _ = out.flush();
+        if limit == Some(i + 1) {
+            break;
+        }
+    }
+    EXIT_OK
+}
+
+/// Runs a subcommand and returns the process exit code.
+pub fn run(command: Command, services: &AppServices, out: &mut dyn Write) -> i32 {
+    let source = services.release_monitor.source().to_string();
+    match command {
+        Command::Check => {
+            let latest = match services.version_checker.get_latest_version() {
+                Ok(v) => v,
+                Err(e) => {
+                    print_json(out, &ErrorReport { source, error: e.to_string() });
+                    return if e.downcast_ref::<UnreachableSourceError>().is_some() { EXIT_UNREACHABLE } else { EXIT_ERROR };
+                }
+            };
+
+            let acknowledged = services.version_updater.get_version();
+            let up_to_date = latest == BuildVersion::default() || latest == acknowledged;
+            print_json(out, &CheckReport { source, latest, acknowledged, up_to_date });
+            if up_to_date { EXIT_OK } else { EXIT_UNACKNOWLEDGED }
+        }
+        Command::Ack { version, note } => {
+            if !services.acknowledge(&version, note) {
+                print_json(out, &ErrorReport { source, error: format!("{} is not a version.", version) });
+                return EXIT_ERROR;
+            }
+
+            print_json(out, &AckReport { source, acknowledged: services.version_updater.get_version() });
+            EXIT_OK
+        }
+        Command::Reset => {
+            let acknowledged = services.release_monitor.undo_acknowledge();
+            print_json(out, &AckReport { source, acknowledged });
+            EXIT_OK
+        }
+        Command::Watch { .. } => watch(services, out, None),
+        #[cfg(unix)]
+        Command::Daemon { socket } => {
+            let path = socket.unwrap_or_else(daemon::default_socket_path);
+            let listener = match daemon::bind(&path) {
+                Ok(l) => l,
+                Err(e) => {
+                    print_json(out, &ErrorReport { source, error: e.to_string() });
+                    return EXIT_ERROR;
+                }
+            };
+
+            let _ = services.release_monitor.scan_once(Utc::now());
+            match services.release_monitor.start() {
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Failed to start monitor! Error: {}", e);
+                    return EXIT_ERROR;
+                }
+            }
+
+            info!("Listening on {}.", path.display());
+            daemon::serve(&listener, services);
+            EXIT_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use clap::Parser;
+    use crate::build_version::BuildVersion;
+    use crate::cli::{run, watch, Cli, Command, EXIT_ERROR, EXIT_OK, EXIT_UNACKNOWLEDGED, EXIT_UNREACHABLE};
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::services::AppServices;
+    use crate::state_store::SqliteStateStore;
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater};
+
+    fn services() -> (Arc<FakeVersionChecker>, AppServices) {
+        let checker = Arc::new(FakeVersionChecker::default());
+        let updater = Arc::new(FakeVersionUpdater::new("a"));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        let release_monitor = Arc::new(ReleaseMonitor::new("a", checker.clone(), updater.clone(), store, 60));
+        (checker.clone(), AppServices { release_monitor, version_checker: checker, version_updater: updater })
+    }
+
+    fn output(command: Command, services: &AppServices) -> (i32, serde_json::Value) {
+        let mut out = Vec::new();
+        let code = run(command, services, &mut out);
+        (code, serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn check_exits_with_whether_the_latest_version_is_acknowledged() {
+        let (checker, services) = services();
+        checker.set_version(BuildVersion::parse("R1.02.03T04").unwrap());
+
+        let (code, json) = output(Command::Check, &services);
+        assert_eq!(code, EXIT_UNACKNOWLEDGED);
+        assert_eq!(json["latest"], "R1.02.03T04");
+        assert_eq!(json["up_to_date"], false);
+
+        let (code, json) = output(Command::Ack { version: String::from("R1.02.03T04"), note: None }, &services);
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(json["acknowledged"], "R1.02.03T04");
+
+        let (code, json) = output(Command::Check, &services);
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(json["up_to_date"], true);
+
+        let (code, json) = output(Command::Reset, &services);
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(json["acknowledged"], "R0.00.00T00");
+    }
+
+    #[test]
+    fn reports_errors_with_distinct_exit_codes() {
+        let (checker, services) = services();
+
+        checker.unreachable("//share/builds");
+        let (code, json) = output(Command::Check, &services);
+        assert_eq!(code, EXIT_UNREACHABLE);
+        assert_eq!(json["source"], "a");
+
+        checker.fail("bad listing");
+        assert_eq!(output(Command::Check, &services).0, EXIT_ERROR);
+        assert_eq!(output(Command::Ack { version: String::from("latest"), note: None }, &services).0, EXIT_ERROR);
+    }
+
+    #[test]
+    fn watch_accepts_json() {
+        let cli = Cli::try_parse_from(["release-monitor", "watch", "--json"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Watch { json: true })));
+    }
+
+    #[test]
+    fn watch_prints_events_as_json_lines() {
+        let (checker, services) = services();
+        checker.set_version(BuildVersion::parse("R1.02.03T04").unwrap());
+
+        let mut out = Vec::new();
+        assert_eq!(watch(&services, &mut out, Some(1)), EXIT_OK);
+        let lines: Vec<serde_json::Value> = String::from_utf8(out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["kind"], "version_detected");
+        assert_eq!(lines[0]["payload"]["version"], "R1.02.03T04");
+    }
+}
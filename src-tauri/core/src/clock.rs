@@ -0,0 +1,22 @@
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+/// Where the monitor gets the time from, so tests can run it on a virtual clock.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+    fn sleep(&self, duration: Duration);
+}
+
+#[derive(Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::notifier::Notifier;
+use crate::release::DetectedRelease;
+use crate::template;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout_seconds() -> u64 {
+    600
+}
+
+/// A program to run when a new version is detected, e.g. to download or install it.
+///
+/// The version, source and matched file are passed as `RELEASE_MONITOR_VERSION`,
+/// `RELEASE_MONITOR_SOURCE` and `RELEASE_MONITOR_PATH`, and can be placed in `args` with
+/// `{version}`, `{source}` and `{path}`.
+///
+/// ```toml
+/// [[commands]]
+/// program = "/usr/local/bin/install-build"
+/// args = ["--file", "{path}"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CommandConfig {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Sources to run for. Empty runs for every source.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// The command is killed when it runs longer.
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64
+}
+
+impl CommandConfig {
+    pub fn runs_for(&self, source: &str) -> bool {
+        self.enabled && (self.sources.is_empty() || self.sources.iter().any(|s| s == source))
+    }
+}
+
+pub struct CommandNotifier {
+    config: CommandConfig
+}
+
+impl CommandNotifier {
+    pub fn new(config: CommandConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// Logs each line of a child's output as it arrives.
+fn log_lines<R: Read + Send + 'static>(program: String, stream: R, is_stderr: bool) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if is_stderr {
+                warn!("{}: {}", program, line);
+            } else {
+                info!("{}: {}", program, line);
+            }
+        }
+    })
+}
+
+impl Notifier for CommandNotifier {
+    fn name(&self) -> &str {
+        &self.config.program
+    }
+
+    fn waits_for_gate(&self) -> bool {
+        false
+    }
+
+    fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error> {
+        if !self.config.runs_for(&release.source) {
+            return Ok(());
+        }
+
+        let vars = release.vars();
+        let mut child = Command::new(&self.config.program)
+            .args(self.config.args.iter().map(|a| template::render(a, &vars)))
+            .env("RELEASE_MONITOR_VERSION", release.version.to_string())
+            .env("RELEASE_MONITOR_SOURCE", &release.source)
+            .env("RELEASE_MONITOR_PATH", release.path.as_deref().unwrap_or_default())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let program = self.config.program.clone();
+        let stdout = child.stdout.take().map(|s| log_lines(program.clone(), s, false));
+        let stderr = child.stderr.take().map(|s| log_lines(program.clone(), s, true));
+
+        let deadline = Instant::now() + Duration::from_secs(self.config.timeout_seconds);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!("{} timed out after {}s.", program, self.config.timeout_seconds));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        for handle in stdout.into_iter().chain(stderr) {
+            let _ = handle.join();
+        }
+
+        if !status.success() {
+            return Err(anyhow!("{} exited with {}.", program, status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::time::Instant;
+    use crate::build_version::BuildVersion;
+    use crate::command_hook::{CommandConfig, CommandNotifier};
+    use crate::notifier::Notifier;
+    use crate::release::DetectedRelease;
+
+    fn command(args: &[&str], timeout_seconds: u64) -> CommandConfig {
+        CommandConfig {
+            program: String::from("sh"),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            enabled: true,
+            sources: vec![String::from("a")],
+            timeout_seconds
+        }
+    }
+
+    #[test]
+    fn passes_the_release_through_env_and_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("R1.02.03T04.zip").to_string_lossy().to_string();
+        let release = DetectedRelease { source: String::from("a"), version: BuildVersion::parse("R1.02.03T04").unwrap(), path: Some(path.clone()), ..Default::default() };
+
+        let script = r#"echo "$RELEASE_MONITOR_VERSION $RELEASE_MONITOR_SOURCE $0" > "$RELEASE_MONITOR_PATH.txt""#;
+        CommandNotifier::new(command(&["-c", script, "{version}"], 10)).notify(&release).unwrap();
+        assert_eq!(fs::read_to_string(format!("{}.txt", path)).unwrap(), "R1.02.03T04 a R1.02.03T04\n");
+
+        assert!(CommandNotifier::new(command(&["-c", "exit 3"], 10)).notify(&release).is_err());
+
+        let other = DetectedRelease { source: String::from("b"), ..release };
+        CommandNotifier::new(command(&["-c", "exit 3"], 10)).notify(&other).unwrap();
+    }
+
+    #[test]
+    fn kills_commands_which_run_too_long() {
+        let release = DetectedRelease { source: String::from("a"), version: BuildVersion::default(), path: None, ..Default::default() };
+        let started = Instant::now();
+        assert!(CommandNotifier::new(command(&["-c", "sleep 10"], 1)).notify(&release).is_err());
+        assert!(started.elapsed().as_secs() < 5);
+    }
+}
@@ -0,0 +1,356 @@
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use figment::{Error, Figment, Metadata, Profile, Provider, Source};
+use figment::providers::{Format, Toml};
+use figment::value::{Dict, Map, Value};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use crate::chat::ChatConfig;
+use crate::command_hook::CommandConfig;
+use crate::download::DownloadConfig;
+use crate::email::EmailConfig;
+use crate::notification::NotificationConfig;
+use crate::webhook::WebhookConfig;
+
+pub const APP_CONFIG_FILE: &str = "app.toml";
+
+/// Environment variable which overrides the location of the system-level org config file.
+pub const ORG_CONFIG_ENV: &str = "RELEASE_MONITOR_ORG_CONFIG";
+
+/// How long a fetched org base config is used before it is fetched again.
+const ORG_CONFIG_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VersionCheckerConfig {
+    pub source: String,
+    pub path: String,
+    pub file_regex: String,
+    pub interval_seconds: u32,
+    pub naggy: bool,
+    /// When set, the app shows and acknowledges what the daemon on this socket sees instead of scanning itself.
+    pub daemon_socket: Option<String>,
+    /// Serves the HTTP API on this loopback port. Needs `http_token`.
+    pub http_port: Option<u16>,
+    pub http_token: Option<String>,
+    /// Called whenever a new version is detected.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Team channels to announce new versions in.
+    #[serde(default)]
+    pub chats: Vec<ChatConfig>,
+    /// Mailed whenever a new version is detected.
+    #[serde(default)]
+    pub emails: Vec<EmailConfig>,
+    /// Run whenever a new version is detected.
+    #[serde(default)]
+    pub commands: Vec<CommandConfig>,
+    /// Copies new versions into a local folder.
+    #[serde(default)]
+    pub download: Option<DownloadConfig>,
+    /// Text and snooze length of the desktop notification.
+    #[serde(default)]
+    pub notification: NotificationConfig
+}
+
+impl Default for VersionCheckerConfig {
+    fn default() -> Self {
+        VersionCheckerConfig {
+            source: String::from("default"),
+            path: String::from(r"/Volumes/Data/Test"),
+            file_regex: String::from(r".*.txt"),
+            interval_seconds: 60,
+            naggy: false,
+            daemon_socket: None,
+            http_port: None,
+            http_token: None,
+            webhooks: Vec::new(),
+            chats: Vec::new(),
+            emails: Vec::new(),
+            commands: Vec::new(),
+            download: None,
+            notification: NotificationConfig::default()
+        }
+    }
+}
+
+impl Provider for VersionCheckerConfig {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("Library Config")
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error>  {
+        figment::providers::Serialized::defaults(VersionCheckerConfig::default()).data()
+    }
+
+    fn profile(&self) -> Option<Profile> {
+        // Optionally, a profile that's selected by default.
+        None
+    }
+}
+
+/// The system-level file pointing at the org-wide base config.
+///
+/// ```toml
+/// base_config = "//fileserver/team/release-monitor.toml" # or an http(s) URL
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct OrgConfig {
+    pub base_config: Option<String>
+}
+
+impl OrgConfig {
+    pub fn system_config_file() -> PathBuf {
+        if let Ok(p) = env::var(ORG_CONFIG_ENV) {
+            return PathBuf::from(p);
+        }
+
+        if cfg!(target_os = "windows") {
+            let program_data = env::var("ProgramData").unwrap_or(String::from(r"C:\ProgramData"));
+            PathBuf::from(program_data).join("decryptology").join("releasemonitor").join("org.toml")
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from("/Library/Application Support/com.decryptology.releasemonitor/org.toml")
+        } else {
+            PathBuf::from("/etc/release-monitor/org.toml")
+        }
+    }
+
+    pub fn load(path: &Path) -> OrgConfig {
+        if !path.exists() {
+            return OrgConfig::default();
+        }
+
+        match Figment::from(Toml::file(path)).extract::<OrgConfig>() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Unable to read org config {}. Error: {}", path.display(), e);
+                OrgConfig::default()
+            }
+        }
+    }
+}
+
+/// Org-provided base config, read from a shared path or URL.
+#[derive(Clone)]
+pub struct OrgBaseConfig {
+    location: String,
+    content: String
+}
+
+impl OrgBaseConfig {
+    pub fn fetch(location: &str) -> Result<OrgBaseConfig, anyhow::Error> {
+        let content = if location.starts_with("http://") || location.starts_with("https://") {
+            ureq::get(location)
+                .timeout(Duration::from_secs(10))
+                .call()?
+                .into_string()?
+        } else {
+            fs::read_to_string(location)?
+        };
+
+        Ok(OrgBaseConfig { location: location.into(), content })
+    }
+}
+
+impl Provider for OrgBaseConfig {
+    fn metadata(&self) -> Metadata {
+        Metadata::from("Org Config", Source::Custom(self.location.clone()))
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        Toml::string(&self.content).data()
+    }
+}
+
+/// A single merged config value and where it came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub origin: String,
+    pub source: Option<String>
+}
+
+/// Shown instead of secrets in [`config_entries`].
+pub const REDACTED: &str = "********";
+
+/// Keeps the org base config between config reads, so a slow share or URL doesn't stall every
+/// one. Fetched again after a while or when refreshed, which also retries a failed fetch.
+pub struct OrgConfigCache {
+    file: PathBuf,
+    fetched: Mutex<Option<(Instant, Option<OrgBaseConfig>)>>
+}
+
+impl Default for OrgConfigCache {
+    fn default() -> Self {
+        Self::new(&OrgConfig::system_config_file())
+    }
+}
+
+impl OrgConfigCache {
+    /// `file` is the system-level file pointing at the base config.
+    pub fn new(file: &Path) -> Self {
+        Self { file: file.into(), fetched: Mutex::new(None) }
+    }
+
+    /// Fetches the base config again on the next read.
+    pub fn refresh(&self) {
+        *self.fetched.lock().unwrap() = None;
+    }
+
+    pub fn get(&self) -> Option<OrgBaseConfig> {
+        let mut fetched = self.fetched.lock().unwrap();
+        match &*fetched {
+            Some((at, base)) if at.elapsed() < ORG_CONFIG_TTL => base.clone(),
+            _ => {
+                let base = self.fetch();
+                *fetched = Some((Instant::now(), base.clone()));
+                base
+            }
+        }
+    }
+
+    fn fetch(&self) -> Option<OrgBaseConfig> {
+        let location = OrgConfig::load(&self.file).base_config?;
+        match OrgBaseConfig::fetch(&location) {
+            Ok(base) => {
+                info!("Using org config from {}.", location);
+                Some(base)
+            }
+            Err(e) => {
+                error!("Unable to fetch org config from {}. Error: {}", location, e);
+                None
+            }
+        }
+    }
+}
+
+/// Builds the layered config: library defaults, then the org base config, then the user's `app.toml`.
+pub fn figment(config_path: &Path, org: &OrgConfigCache) -> Figment {
+    let mut figment = Figment::from(VersionCheckerConfig::default());
+    if let Some(base) = org.get() {
+        figment = figment.merge(base);
+    }
+
+    figment.merge(Toml::file(config_path.join(APP_CONFIG_FILE)))
+}
+
+/// Whether `key` inside the top-level `section` holds a credential. Chat URLs embed their token.
+fn is_secret(section: &str, key: &str) -> bool {
+    matches!((section, key), ("", "http_token") | ("webhooks", "secret") | ("emails", "password") | ("chats", "url"))
+}
+
+fn redact(section: &str, key: &str, value: Value) -> Value {
+    match value {
+        Value::String(tag, _) if is_secret(section, key) => Value::String(tag, REDACTED.into()),
+        Value::Array(tag, values) => Value::Array(tag, values.into_iter().map(|v| redact(section, key, v)).collect()),
+        Value::Dict(tag, dict) => Value::Dict(tag, dict.into_iter().map(|(k, v)| {
+            let v = redact(if section.is_empty() { key } else { section }, &k, v);
+            (k, v)
+        }).collect()),
+        v => v
+    }
+}
+
+/// Lists every top-level key of the merged config together with the provider it came from. Secrets are redacted.
+pub fn config_entries(figment: &Figment) -> Vec<ConfigEntry> {
+    let mut entries = Vec::new();
+    let dict = match figment.data() {
+        Ok(mut data) => data.remove(&Profile::Default).unwrap_or_default(),
+        Err(e) => {
+            error!("Unable to read config! Error: {}", e);
+            return entries;
+        }
+    };
+
+    for (key, value) in dict {
+        let (origin, source) = match figment.find_metadata(&key) {
+            None => (String::from("Unknown"), None),
+            Some(m) => (m.name.to_string(), m.source.as_ref().map(|s| s.to_string()))
+        };
+        let value = match redact("", &key, value) {
+            Value::String(_, s) => s,
+            v => serde_json::to_string(&v).unwrap_or_default()
+        };
+
+        entries.push(ConfigEntry { key, value, origin, source });
+    }
+
+    entries
+}
+
+/// Updates a single key in the user's `app.toml`, leaving every other layer untouched.
+pub fn write_user_value<V: Into<toml::Value>>(config_path: &Path, key: &str, value: V) -> Result<(), anyhow::Error> {
+    let config_file = config_path.join(APP_CONFIG_FILE);
+    let mut table = match fs::read_to_string(&config_file) {
+        Ok(s) => s.parse::<toml::Table>()?,
+        Err(_) => toml::Table::new()
+    };
+
+    table.insert(key.into(), value.into());
+    fs::create_dir_all(config_path)?;
+    fs::write(&config_file, toml::to_string_pretty(&table)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use figment::Figment;
+    use figment::providers::{Format, Toml};
+    use tempfile::tempdir;
+    use crate::config::{config_entries, figment, OrgConfigCache, REDACTED};
+
+    #[test]
+    fn redacts_secrets() {
+        let figment = Figment::from(Toml::string(r#"
+            source = "a"
+            http_token = "token"
+
+            [[webhooks]]
+            url = "https://example.com/hook"
+            secret = "hmac"
+
+            [[chats]]
+            kind = "slack"
+            url = "https://hooks.slack.com/services/T/B/X"
+
+            [[emails]]
+            host = "smtp.example.com"
+            password = "hunter2"
+        "#));
+
+        let entries = config_entries(&figment);
+        let value = |key: &str| entries.iter().find(|e| e.key == key).unwrap().value.clone();
+        assert_eq!(value("source"), "a");
+        assert_eq!(value("http_token"), REDACTED);
+        for (key, secret) in [("webhooks", "hmac"), ("chats", "hooks.slack.com"), ("emails", "hunter2")] {
+            assert!(!value(key).contains(secret), "{} leaks {}", key, secret);
+            assert!(value(key).contains(REDACTED));
+        }
+        assert!(value("webhooks").contains("https://example.com/hook"));
+    }
+
+    #[test]
+    fn org_config_is_cached_until_refreshed() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("base.toml");
+        let org_file = dir.path().join("org.toml");
+        fs::write(&org_file, format!("base_config = {:?}", base.to_string_lossy())).unwrap();
+        let org = OrgConfigCache::new(&org_file);
+        let source = |org: &OrgConfigCache| figment(dir.path(), org).extract_inner::<String>("source").unwrap();
+
+        // Not there yet at startup.
+        assert_eq!(source(&org), "default");
+        fs::write(&base, "source = \"team\"").unwrap();
+        assert_eq!(source(&org), "default");
+        org.refresh();
+        assert_eq!(source(&org), "team");
+
+        fs::write(&base, "source = \"other\"").unwrap();
+        assert_eq!(source(&org), "team");
+        org.refresh();
+        assert_eq!(source(&org), "other");
+    }
+}
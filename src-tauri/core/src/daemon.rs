@@ -0,0 +1,431 @@
+//! JSON-RPC 2.0 over a Unix domain socket, one request per line, so a headless monitor can be
+//! queried and acknowledged by scripts or by the GUI.
+//!
+//! A `subscribe` request turns its connection into an event stream: every event the monitor
+//! publishes follows as an `event` notification, one per line.
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{error, info};
+use crate::ack_history::AckRecord;
+use crate::build_version::BuildVersion;
+use crate::publisher::{Event, Message};
+use crate::release::DetectedRelease;
+use crate::services::{AppServices, StatusReport};
+use crate::version_checker::VersionChecker;
+use crate::version_updater::VersionUpdater;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a subscribed client waits before reconnecting to a daemon which went away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// `$XDG_RUNTIME_DIR/release-monitor.sock`, or the same in the temp dir.
+pub fn default_socket_path() -> PathBuf {
+    let dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or(env::temp_dir());
+    dir.join("release-monitor.sock")
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Self {
+        Self { jsonrpc: String::from("2.0"), id, result: Some(result), error: None }
+    }
+
+    fn error(id: Value, code: i64, message: &str) -> Self {
+        Self { jsonrpc: String::from("2.0"), id, result: None, error: Some(RpcError { code, message: message.into() }) }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AckParams {
+    version: String,
+    #[serde(default)]
+    note: Option<String>
+}
+
+/// Answers a single request. Supports `status`, `ack`, `reset` and `history`.
+pub fn handle(services: &AppServices, request: Request) -> Response {
+    let monitor = &services.release_monitor;
+    match request.method.as_str() {
+        "status" => Response::result(request.id, json!(services.status())),
+        "ack" => {
+            let params = match serde_json::from_value::<AckParams>(request.params) {
+                Ok(p) => p,
+                Err(e) => return Response::error(request.id, INVALID_PARAMS, &e.to_string())
+            };
+            if !services.acknowledge(&params.version, params.note) {
+                return Response::error(request.id, INVALID_PARAMS, &format!("{} is not a version.", params.version));
+            }
+            Response::result(request.id, json!(services.version_updater.get_version()))
+        }
+        "reset" => Response::result(request.id, json!(monitor.undo_acknowledge())),
+        "history" => Response::result(request.id, json!(monitor.ack_history())),
+        m => Response::error(request.id, METHOD_NOT_FOUND, &format!("Unknown method {}.", m))
+    }
+}
+
+/// Binds the control socket, replacing a stale one left behind by a daemon which didn't exit cleanly.
+pub fn bind(path: &Path) -> Result<UnixListener, anyhow::Error> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(anyhow!("A daemon is already listening on {}.", path.display()));
+        }
+        fs::remove_file(path)?;
+    }
+
+    Ok(UnixListener::bind(path)?)
+}
+
+fn write_line<T: Serialize>(writer: &mut UnixStream, value: &T) -> Result<(), anyhow::Error> {
+    let mut out = serde_json::to_string(value)?;
+    out.push('\n');
+    writer.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Sends every event to the subscriber until it hangs up.
+fn stream_events(services: &AppServices, id: Value, mut writer: UnixStream) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let _subscription = services.release_monitor.subscribe_many(&Event::ALL, move |m| {
+        let _ = tx.lock().unwrap().send(m.clone());
+    });
+    if write_line(&mut writer, &Response::result(id, json!(true))).is_err() {
+        return;
+    }
+
+    for message in rx {
+        let notification = json!({ "jsonrpc": "2.0", "method": "event", "params": message });
+        if write_line(&mut writer, &notification).is_err() {
+            break;
+        }
+    }
+}
+
+fn serve_connection(services: &AppServices, stream: UnixStream) {
+    let mut writer = match stream.try_clone() {
+        Ok(w) => w,
+        Err(e) => {
+            error!("Unable to accept connection! Error: {}", e);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(l) if l.trim().is_empty() => continue,
+            Ok(l) => l,
+            Err(_) => break
+        };
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) if request.method == "subscribe" => {
+                stream_events(services, request.id, writer);
+                return;
+            }
+            Ok(request) => handle(services, request),
+            Err(e) => Response::error(Value::Null, PARSE_ERROR, &e.to_string())
+        };
+        if write_line(&mut writer, &response).is_err() {
+            break;
+        }
+    }
+}
+
+/// Serves connections until the listener fails, each on its own thread.
+pub fn serve(listener: &UnixListener, services: &AppServices) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(s) => {
+                let services = services.clone();
+                thread::spawn(move || serve_connection(&services, s));
+            }
+            Err(e) => {
+                error!("Control socket failed! Error: {}", e);
+                break;
+            }
+        }
+    }
+    info!("Control socket closed.");
+}
+
+/// Talks to a running daemon. Also stands in for the checker and updater so the GUI can show and
+/// acknowledge what the daemon sees.
+pub struct DaemonClient {
+    path: PathBuf
+}
+
+impl DaemonClient {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, anyhow::Error> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string();
+        request.push('\n');
+        stream.write_all(request.as_bytes())?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let response: Response = serde_json::from_str(&line)?;
+        match (response.result, response.error) {
+            (_, Some(e)) => Err(anyhow!("{} ({})", e.message, e.code)),
+            (Some(r), None) => Ok(r),
+            (None, None) => Ok(Value::Null)
+        }
+    }
+
+    pub fn status(&self) -> Result<StatusReport, anyhow::Error> {
+        Ok(serde_json::from_value(self.call("status", Value::Null)?)?)
+    }
+
+    /// Calls `func` with every event the daemon publishes, on a background thread. Reconnects
+    /// when the daemon goes away.
+    pub fn subscribe<F>(&self, func: F)
+        where F: Fn(Message) + Send + 'static {
+        let path = self.path.clone();
+        thread::spawn(move || loop {
+            match Self::follow(&path, &func) {
+                Ok(_) => {
+                    info!("Daemon at {} closed the event stream.", path.display());
+                }
+                Err(e) => {
+                    error!("Unable to follow daemon at {}! Error: {}", path.display(), e);
+                }
+            }
+            thread::sleep(RECONNECT_DELAY);
+        });
+    }
+
+    fn follow<F: Fn(Message)>(path: &Path, func: &F) -> Result<(), anyhow::Error> {
+        let mut stream = UnixStream::connect(path)?;
+        let mut request = json!({ "jsonrpc": "2.0", "id": 1, "method": "subscribe" }).to_string();
+        request.push('\n');
+        stream.write_all(request.as_bytes())?;
+
+        let mut lines = BufReader::new(stream).lines();
+        let response: Response = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Ok(())
+        };
+        if let Some(e) = response.error {
+            return Err(anyhow!("{} ({})", e.message, e.code));
+        }
+        info!("Following events of daemon at {}.", path.display());
+
+        for line in lines {
+            let notification: Request = serde_json::from_str(&line?)?;
+            match serde_json::from_value::<Message>(notification.params) {
+                Ok(message) => func(message),
+                Err(e) => {
+                    error!("Unable to read event from daemon! Error: {}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VersionChecker for DaemonClient {
+    fn get_latest_version(&self) -> Result<BuildVersion, anyhow::Error> {
+        Ok(self.status()?.latest)
+    }
+
+    fn get_latest_release(&self, _source: &str) -> Result<DetectedRelease, anyhow::Error> {
+        self.status()?.release.ok_or(anyhow!("The daemon hasn't found a release yet."))
+    }
+}
+
+impl VersionUpdater for DaemonClient {
+    fn get_version(&self) -> BuildVersion {
+        match self.status() {
+            Ok(s) => s.acknowledged,
+            Err(e) => {
+                error!("Unable to get status from daemon! Error: {}", e);
+                BuildVersion::default()
+            }
+        }
+    }
+
+    fn set_version(&self, version: BuildVersion, note: Option<String>) {
+        match self.call("ack", json!({ "version": version, "note": note })) {
+            Ok(_) => {}
+            Err(e) => {
+                error!("Unable to acknowledge {} through daemon! Error: {}", version, e);
+            }
+        }
+    }
+
+    fn undo(&self) -> BuildVersion {
+        match self.call("reset", Value::Null).and_then(|v| Ok(serde_json::from_value(v)?)) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Unable to reset through daemon! Error: {}", e);
+                BuildVersion::default()
+            }
+        }
+    }
+
+    fn history(&self) -> Vec<AckRecord> {
+        match self.call("history", Value::Null).and_then(|v| Ok(serde_json::from_value(v)?)) {
+            Ok(h) => h,
+            Err(e) => {
+                error!("Unable to get history from daemon! Error: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use chrono::Utc;
+    use serde_json::{json, Value};
+    use crate::build_version::BuildVersion;
+    use crate::daemon::{bind, handle, serve, DaemonClient, Request, METHOD_NOT_FOUND};
+    use crate::publisher::{Event, Message};
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::services::AppServices;
+    use crate::source_state::SourceState;
+    use crate::state_store::{DetectionStore, SqliteStateStore};
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater};
+    use crate::version_checker::VersionChecker;
+    use crate::version_updater::VersionUpdater;
+
+    fn services() -> AppServices {
+        let checker = Arc::new(FakeVersionChecker::default());
+        checker.set_version(BuildVersion::parse("R1.02.03T04").unwrap());
+        let updater = Arc::new(FakeVersionUpdater::new("a"));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        store.record_seen(BuildVersion::parse("R1.02.03T04").unwrap());
+        let release_monitor = Arc::new(ReleaseMonitor::new("a", checker.clone(), updater.clone(), store, 60));
+        AppServices { release_monitor, version_checker: checker, version_updater: updater }
+    }
+
+    #[test]
+    fn rejects_unknown_methods() {
+        let request = Request { id: json!(7), method: String::from("explode"), params: Value::Null };
+        let response = handle(&services(), request);
+        assert_eq!(response.id, json!(7));
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn client_acknowledges_through_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.sock");
+        let listener = bind(&path).unwrap();
+        assert!(bind(&path).is_err());
+        thread::spawn(move || serve(&listener, &services()));
+
+        let client = DaemonClient::new(&path);
+        let status = client.status().unwrap();
+        assert_eq!(status.source, "a");
+        assert_eq!(status.state, SourceState::Reminding(BuildVersion::parse("R1.02.03T04").unwrap()));
+        assert_eq!(client.get_latest_version().unwrap(), status.latest);
+
+        client.set_version(status.latest, Some(String::from("installed")));
+        assert_eq!(client.get_version(), status.latest);
+        assert_eq!(client.history()[0].note.as_deref(), Some("installed"));
+        assert_eq!(client.undo(), BuildVersion::default());
+
+        assert!(client.call("ack", json!({ "version": "nope" })).is_err());
+    }
+
+    #[test]
+    fn subscribed_clients_get_every_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.sock");
+        let listener = bind(&path).unwrap();
+        let services = services();
+        let served = services.clone();
+        thread::spawn(move || serve(&listener, &served));
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        DaemonClient::new(&path).subscribe(move |m| { let _ = tx.lock().unwrap().send(m); });
+        while services.release_monitor.event_metrics().is_empty() {
+            thread::yield_now();
+        }
+
+        services.release_monitor.scan_once(Utc::now()).unwrap();
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Message::VersionReminder(r) => assert_eq!((r.version, r.count), (BuildVersion::parse("R1.02.03T04").unwrap(), 1)),
+            m => panic!("Expected a reminder, got {:?}", m)
+        }
+        services.release_monitor.undo_acknowledge();
+        assert!(matches!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), Message::AckReset(_)));
+    }
+
+    #[test]
+    fn following_monitors_publish_acknowledgements_once_and_ask_the_daemon_for_the_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.sock");
+        let listener = bind(&path).unwrap();
+        let services = services();
+        services.release_monitor.scan_once(Utc::now()).unwrap();
+        let served = services.clone();
+        thread::spawn(move || serve(&listener, &served));
+
+        let client = Arc::new(DaemonClient::new(&path));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        let follower = Arc::new(ReleaseMonitor::new("a", client.clone(), client.clone(), store, 60).following());
+        assert_eq!(follower.latest_release(), services.release_monitor.latest_release());
+        assert!(follower.latest_release().is_some());
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let _acknowledged = follower.subscribe(Event::VersionAcknowledged, move |m| { let _ = tx.lock().unwrap().send(m.clone()); });
+        let passed_on = follower.clone();
+        client.subscribe(move |m| passed_on.publish(m));
+        while services.release_monitor.event_metrics().is_empty() {
+            thread::yield_now();
+        }
+
+        follower.acknowledge(BuildVersion::parse("R1.02.03T04").unwrap(), None);
+        assert!(matches!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), Message::VersionAcknowledged(_)));
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Copies newly detected builds from the share into a local folder so they're ready to install.
+//!
+//! Copies go to a `.part` file first. When the build has a sibling `.sha256` file an interrupted
+//! copy picks up where it left off and the result is checked against it before it's kept. Without
+//! one there's no way to tell the partial copy is good, so it starts over.
+//!
+//! The folder may be shared with other files, so the builds copied are listed in a manifest and
+//! retention only ever deletes those.
+
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use crate::build_version::BuildVersion;
+use crate::notifier::Notifier;
+use crate::release::DetectedRelease;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+const MANIFEST: &str = ".release-monitor-downloads";
+
+fn default_keep() -> usize {
+    3
+}
+
+/// ```toml
+/// [download]
+/// dir = "/Users/me/Builds"
+/// keep = 3
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DownloadConfig {
+    pub dir: String,
+    /// How many downloaded builds to keep in `dir`. Older ones are deleted after a copy. Other
+    /// files in `dir` are left alone.
+    #[serde(default = "default_keep")]
+    pub keep: usize
+}
+
+/// How far a copy has got.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DownloadProgress {
+    pub source: String,
+    pub version: BuildVersion,
+    /// Where the build is being copied to.
+    pub path: String,
+    pub copied: u64,
+    pub total: u64,
+    /// Set once the copy has been verified and kept.
+    pub done: bool
+}
+
+pub struct ArtifactDownloader {
+    config: DownloadConfig,
+    on_progress: Box<dyn Fn(DownloadProgress) + Send + Sync>
+}
+
+impl ArtifactDownloader {
+    pub fn new(config: DownloadConfig) -> Self {
+        Self { config, on_progress: Box::new(|_| {}) }
+    }
+
+    /// Called as the copy progresses, at most once per percent.
+    pub fn with_progress<F>(mut self, on_progress: F) -> Self
+        where F: Fn(DownloadProgress) + Send + Sync + 'static {
+        self.on_progress = Box::new(on_progress);
+        self
+    }
+
+    /// Copies `source` into the download folder and returns where it went.
+    pub fn download(&self, release: &DetectedRelease, source: &Path) -> Result<PathBuf, anyhow::Error> {
+        let name = source.file_name().ok_or_else(|| anyhow!("{} is not a file.", source.display()))?;
+        let dir = Path::new(&self.config.dir);
+        fs::create_dir_all(dir)?;
+        let target = dir.join(name);
+        if target.exists() {
+            info!("{} was already downloaded.", target.display());
+            return Ok(target);
+        }
+
+        let mut part_name = name.to_os_string();
+        part_name.push(".part");
+        let part = dir.join(part_name);
+
+        let progress = |copied: u64, total: u64, done: bool| DownloadProgress {
+            source: release.source.clone(),
+            version: release.version,
+            path: target.to_string_lossy().to_string(),
+            copied,
+            total,
+            done
+        };
+
+        let expected = expected_checksum(source)?;
+        let mut input = File::open(source)?;
+        let total = input.metadata()?.len();
+        let mut output = OpenOptions::new().create(true).append(true).open(&part)?;
+        let mut copied = output.metadata()?.len();
+        if copied > 0 && (copied > total || expected.is_none()) {
+            info!("Restarting copy of {}, the partial copy can't be verified.", source.display());
+            output.set_len(0)?;
+            copied = 0;
+        }
+        if copied > 0 {
+            info!("Resuming copy of {} at {} of {} bytes.", source.display(), copied, total);
+        }
+        input.seek(SeekFrom::Start(copied))?;
+
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut last_percent = None;
+        loop {
+            let read = input.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            output.write_all(&buffer[..read])?;
+            copied += read as u64;
+
+            let percent = copied.saturating_mul(100).checked_div(total).unwrap_or(100);
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                (self.on_progress)(progress(copied, total, false));
+            }
+        }
+        output.sync_all()?;
+        drop(output);
+
+        if let Some(expected) = expected {
+            let actual = checksum(&part)?;
+            if !actual.eq_ignore_ascii_case(&expected) {
+                fs::remove_file(&part)?;
+                return Err(anyhow!("{} does not match its checksum.", source.display()));
+            }
+        }
+
+        fs::rename(&part, &target)?;
+        let mut downloaded = read_manifest(dir)?;
+        downloaded.retain(|n| n.as_str() != name);
+        downloaded.push(name.to_string_lossy().to_string());
+        write_manifest(dir, &downloaded)?;
+        (self.on_progress)(progress(copied, total, true));
+        self.prune(dir)?;
+        Ok(target)
+    }
+
+    /// Deletes all but the newest `keep` of the builds this downloader copied.
+    fn prune(&self, dir: &Path) -> Result<(), anyhow::Error> {
+        let mut builds = Vec::new();
+        for name in read_manifest(dir)? {
+            let path = dir.join(&name);
+            // Gone already, e.g. deleted by hand.
+            let Ok(metadata) = fs::metadata(&path) else { continue };
+            if metadata.is_file() {
+                builds.push((metadata.modified()?, name));
+            }
+        }
+
+        builds.sort_by_key(|b| Reverse(b.0));
+        let mut kept = Vec::new();
+        for (i, (_, name)) in builds.into_iter().enumerate() {
+            if i < self.config.keep {
+                kept.push(name);
+                continue;
+            }
+            let path = dir.join(&name);
+            info!("Removing old build {}.", path.display());
+            fs::remove_file(path)?;
+        }
+        kept.reverse();
+        write_manifest(dir, &kept)
+    }
+}
+
+/// The names of the builds copied into `dir`, oldest first.
+fn read_manifest(dir: &Path) -> Result<Vec<String>, anyhow::Error> {
+    match fs::read_to_string(dir.join(MANIFEST)) {
+        Ok(content) => Ok(content.lines().filter(|l| !l.is_empty()).map(String::from).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into())
+    }
+}
+
+fn write_manifest(dir: &Path, names: &[String]) -> Result<(), anyhow::Error> {
+    let mut content = String::new();
+    for name in names {
+        content.push_str(name);
+        content.push('\n');
+    }
+    fs::write(dir.join(MANIFEST), content)?;
+    Ok(())
+}
+
+/// The hash in `<file>.sha256`, if there is one. Accepts both a bare hash and `sha256sum` output.
+fn expected_checksum(source: &Path) -> Result<Option<String>, anyhow::Error> {
+    let mut name = source.as_os_str().to_os_string();
+    name.push(".sha256");
+    let path = PathBuf::from(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)?;
+    match content.split_whitespace().next() {
+        Some(hash) => Ok(Some(hash.to_string())),
+        None => Err(anyhow!("{} is empty.", path.display()))
+    }
+}
+
+fn checksum(path: &Path) -> Result<String, anyhow::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+impl Notifier for ArtifactDownloader {
+    fn name(&self) -> &str {
+        "download"
+    }
+
+    fn waits_for_gate(&self) -> bool {
+        false
+    }
+
+    fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error> {
+        let source = match &release.path {
+            Some(p) => PathBuf::from(p),
+            None => return Err(anyhow!("The checker didn't say where {} is.", release.version))
+        };
+        let target = self.download(release, &source)?;
+        info!("Downloaded {} to {}.", release.version, target.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use sha2::{Digest, Sha256};
+    use crate::build_version::BuildVersion;
+    use crate::download::{ArtifactDownloader, DownloadConfig, DownloadProgress};
+    use crate::release::DetectedRelease;
+
+    fn release(version: &str) -> DetectedRelease {
+        DetectedRelease { source: String::from("a"), version: BuildVersion::parse(version).unwrap(), path: None, ..Default::default() }
+    }
+
+    fn downloader(dir: &Path, keep: usize) -> ArtifactDownloader {
+        ArtifactDownloader::new(DownloadConfig { dir: dir.to_string_lossy().to_string(), keep })
+    }
+
+    #[test]
+    fn resumes_verified_copies() {
+        let share = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        let content = vec![7u8; 3 * 1024 * 1024 + 5];
+        let build = share.path().join("R1.02.03T04.zip");
+        fs::write(&build, &content).unwrap();
+        fs::write(share.path().join("R1.02.03T04.zip.sha256"), format!("{}  R1.02.03T04.zip\n", hex::encode(Sha256::digest(&content)))).unwrap();
+        // An interrupted earlier copy.
+        fs::write(local.path().join("R1.02.03T04.zip.part"), &content[..1024 * 1024]).unwrap();
+
+        let events: Arc<Mutex<Vec<DownloadProgress>>> = Arc::new(Mutex::new(Vec::new()));
+        let e = events.clone();
+        let target = downloader(local.path(), 2)
+            .with_progress(move |p| e.lock().unwrap().push(p))
+            .download(&release("R1.02.03T04"), &build)
+            .unwrap();
+
+        assert_eq!(fs::read(&target).unwrap(), content);
+        let events = events.lock().unwrap();
+        assert_eq!(events[0].copied, 2 * 1024 * 1024);
+        assert!(events.last().unwrap().done);
+    }
+
+    #[test]
+    fn restarts_copies_which_cannot_be_verified() {
+        let share = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        let build = share.path().join("R1.02.03T04.zip");
+        fs::write(&build, b"the build").unwrap();
+        fs::write(local.path().join("R1.02.03T04.zip.part"), b"garbage").unwrap();
+
+        let target = downloader(local.path(), 2).download(&release("R1.02.03T04"), &build).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"the build");
+    }
+
+    #[test]
+    fn only_removes_old_builds_it_downloaded() {
+        let share = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        fs::write(local.path().join("notes.txt"), b"not a build").unwrap();
+        fs::write(local.path().join("R0.09.00T00.zip"), b"copied by hand").unwrap();
+
+        let downloader = downloader(local.path(), 2);
+        for version in ["R1.00.00T00", "R1.01.00T00", "R1.02.03T04"] {
+            let build = share.path().join(format!("{}.zip", version));
+            fs::write(&build, version).unwrap();
+            downloader.download(&release(version), &build).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let mut kept: Vec<String> = fs::read_dir(local.path()).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        kept.sort();
+        assert_eq!(kept, vec![".release-monitor-downloads", "R0.09.00T00.zip", "R1.01.00T00.zip", "R1.02.03T04.zip", "notes.txt"]);
+    }
+
+    #[test]
+    fn discards_copies_which_do_not_match_the_checksum() {
+        let share = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        let build = share.path().join("R1.02.03T04.zip");
+        fs::write(&build, b"build").unwrap();
+        fs::write(share.path().join("R1.02.03T04.zip.sha256"), "00ff").unwrap();
+
+        assert!(downloader(local.path(), 2).download(&release("R1.02.03T04"), &build).is_err());
+        assert_eq!(fs::read_dir(local.path()).unwrap().count(), 0);
+    }
+}
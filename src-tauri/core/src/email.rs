@@ -0,0 +1,158 @@
+use std::time::Duration;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use crate::notifier::Notifier;
+use crate::release::DetectedRelease;
+use crate::template;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Implicit TLS, port 465 by default.
+    Tls,
+    /// Upgrades with STARTTLS, port 587 by default.
+    #[default]
+    StartTls,
+    /// Plaintext, port 25 by default. Only for relays on a trusted network.
+    None
+}
+
+fn default_subject() -> String {
+    String::from("New build {version} on {source}")
+}
+
+fn default_body() -> String {
+    String::from("Build {version} is available on {source}.\n\n{path}\n")
+}
+
+/// Mails new versions to people who don't run the app.
+///
+/// ```toml
+/// [[emails]]
+/// host = "smtp.example.com"
+/// username = "release-monitor"
+/// password = "..."
+/// from = "Release Monitor <release-monitor@example.com>"
+/// to = ["team-leads@example.com"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EmailConfig {
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// With `{version}`, `{source}` and `{path}` placeholders, like `body`.
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    #[serde(default = "default_body")]
+    pub body: String
+}
+
+pub struct EmailNotifier {
+    config: EmailConfig,
+    name: String
+}
+
+impl EmailNotifier {
+    pub fn new(config: EmailConfig) -> Self {
+        let name = format!("email to {}", config.to.join(", "));
+        Self { config, name }
+    }
+
+    fn message(&self, release: &DetectedRelease) -> Result<Message, anyhow::Error> {
+        let vars = release.vars();
+        let mut builder = Message::builder()
+            .from(self.config.from.parse::<Mailbox>()?)
+            .subject(template::render(&self.config.subject, &vars));
+        for to in &self.config.to {
+            builder = builder.to(to.parse::<Mailbox>()?);
+        }
+        Ok(builder.body(template::render(&self.config.body, &vars))?)
+    }
+
+    fn transport(&self) -> Result<SmtpTransport, anyhow::Error> {
+        let host = self.config.host.as_str();
+        let mut builder = match self.config.security {
+            SmtpSecurity::Tls => SmtpTransport::relay(host)?,
+            SmtpSecurity::StartTls => SmtpTransport::starttls_relay(host)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(host).port(25)
+        };
+        if let Some(port) = self.config.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &self.config.username {
+            let password = self.config.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(builder.timeout(Some(TIMEOUT)).build())
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error> {
+        let message = self.message(release)?;
+        self.transport()?.send(&message)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_version::BuildVersion;
+    use crate::email::{EmailConfig, EmailNotifier, SmtpSecurity};
+    use crate::notifier::Notifier;
+    use crate::release::DetectedRelease;
+    use crate::testing::smtp_sink;
+
+    #[test]
+    fn sends_a_templated_mail_through_smtp() {
+        let (port, rx) = smtp_sink();
+        let config = EmailConfig {
+            host: String::from("127.0.0.1"),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            username: Some(String::from("monitor")),
+            password: Some(String::from("secret")),
+            from: String::from("Release Monitor <monitor@example.com>"),
+            to: vec![String::from("lead@example.com"), String::from("qa@example.com")],
+            subject: String::from("{version} is out"),
+            body: String::from("Get it from {path}.")
+        };
+        let release = DetectedRelease { source: String::from("a"), version: BuildVersion::parse("R1.02.03T04").unwrap(), path: Some(String::from("/share/R1.02.03T04.zip")), ..Default::default() };
+        EmailNotifier::new(config).notify(&release).unwrap();
+
+        let mail = rx.recv().unwrap();
+        assert!(mail.commands.iter().any(|c| c.starts_with("AUTH PLAIN")));
+        assert!(mail.commands.contains(&String::from("MAIL FROM:<monitor@example.com>")));
+        assert!(mail.commands.contains(&String::from("RCPT TO:<qa@example.com>")));
+        assert!(mail.data.contains("Subject: R1.02.03T04 is out\r\n"));
+        assert!(mail.data.contains("Get it from /share/R1.02.03T04.zip."));
+    }
+
+    #[test]
+    fn rejects_bad_addresses() {
+        let config: EmailConfig = toml::from_str(r#"
+            host = "smtp.example.com"
+            from = "not an address"
+            to = ["lead@example.com"]
+        "#).unwrap();
+        assert_eq!(config.security, SmtpSecurity::StartTls);
+        let release = DetectedRelease { source: String::from("a"), version: BuildVersion::default(), path: None, ..Default::default() };
+        assert!(EmailNotifier::new(config).notify(&release).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! Decides whether anyone may be notified right now. The desktop notification and every notifier
+//! which tells people ask the same gate, so snoozes, quiet hours and do-not-disturb apply
+//! everywhere. Notifiers wait for the gate to open rather than dropping what they would have sent.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration as StdDuration;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use crate::build_version::BuildVersion;
+use crate::clock::{Clock, SystemClock};
+use crate::notification::NotificationConfig;
+use crate::state_store::SqliteStateStore;
+
+/// How often a held back notification checks whether it may go out.
+const RECHECK: StdDuration = StdDuration::from_secs(60);
+const DO_NOT_DISTURB: &str = "do_not_disturb";
+
+/// A daily window without notifications, in local time. May run past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Why a notification was held back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suppressed {
+    DoNotDisturb,
+    Snoozed(DateTime<Utc>),
+    QuietDay(Weekday),
+    QuietHours
+}
+
+impl Display for Suppressed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Suppressed::DoNotDisturb => write!(f, "do not disturb is on"),
+            Suppressed::Snoozed(until) => write!(f, "it is snoozed until {}", until),
+            Suppressed::QuietDay(day) => write!(f, "{} is a quiet day", day),
+            Suppressed::QuietHours => write!(f, "it is quiet hours")
+        }
+    }
+}
+
+struct QuietTimes {
+    hours: Option<QuietHours>,
+    days: Vec<Weekday>
+}
+
+pub struct NotificationGate {
+    clock: Arc<dyn Clock + Send + Sync>,
+    /// Where quiet hours are kept. The machine's own time zone when not set.
+    timezone: Option<FixedOffset>,
+    quiet: Mutex<QuietTimes>,
+    snoozes: Mutex<HashMap<BuildVersion, DateTime<Utc>>>,
+    do_not_disturb: AtomicBool,
+    store: Option<Arc<SqliteStateStore>>
+}
+
+impl NotificationGate {
+    pub fn new(config: &NotificationConfig) -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            timezone: None,
+            quiet: Mutex::new(QuietTimes { hours: config.quiet_hours, days: config.quiet_days.clone() }),
+            snoozes: Mutex::new(HashMap::new()),
+            do_not_disturb: AtomicBool::new(false),
+            store: None
+        }
+    }
+
+    /// Keeps do-not-disturb in `store` so it survives restarts.
+    pub fn with_store(mut self, store: Arc<SqliteStateStore>) -> Self {
+        let on = store.setting(DO_NOT_DISTURB).as_deref() == Some("true");
+        self.do_not_disturb.store(on, Ordering::Relaxed);
+        self.store = Some(store);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock + Send + Sync>, timezone: FixedOffset) -> Self {
+        self.clock = clock;
+        self.timezone = Some(timezone);
+        self
+    }
+
+    /// Picks up changed quiet hours and days.
+    pub fn configure(&self, config: &NotificationConfig) {
+        *self.quiet.lock().unwrap() = QuietTimes { hours: config.quiet_hours, days: config.quiet_days.clone() };
+    }
+
+    pub fn set_do_not_disturb(&self, on: bool) {
+        self.do_not_disturb.store(on, Ordering::Relaxed);
+        if let Some(store) = &self.store {
+            store.set_setting(DO_NOT_DISTURB, &on.to_string());
+        }
+    }
+
+    pub fn do_not_disturb(&self) -> bool {
+        self.do_not_disturb.load(Ordering::Relaxed)
+    }
+
+    /// Holds back notifications about `version` for `duration`.
+    pub fn snooze(&self, version: BuildVersion, duration: Duration) -> DateTime<Utc> {
+        let until = self.clock.now() + duration;
+        self.snoozes.lock().unwrap().insert(version, until);
+        until
+    }
+
+    fn local_time(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        match self.timezone {
+            Some(tz) => now.with_timezone(&tz).naive_local(),
+            None => now.with_timezone(&Local).naive_local()
+        }
+    }
+
+    /// Whether a notification about `version` may go out now.
+    pub fn check(&self, version: BuildVersion) -> Result<(), Suppressed> {
+        if self.do_not_disturb() {
+            return Err(Suppressed::DoNotDisturb);
+        }
+
+        let now = self.clock.now();
+        let mut snoozes = self.snoozes.lock().unwrap();
+        snoozes.retain(|_, until| *until > now);
+        if let Some(until) = snoozes.get(&version) {
+            return Err(Suppressed::Snoozed(*until));
+        }
+        drop(snoozes);
+
+        let local = self.local_time(now);
+        let quiet = self.quiet.lock().unwrap();
+        if quiet.days.contains(&local.weekday()) {
+            return Err(Suppressed::QuietDay(local.weekday()));
+        }
+        match quiet.hours {
+            Some(hours) if hours.contains(local.time()) => Err(Suppressed::QuietHours),
+            _ => Ok(())
+        }
+    }
+
+    /// Blocks until a notification about `version` may go out.
+    pub fn wait(&self, version: BuildVersion) {
+        self.wait_unless(version, || false);
+    }
+
+    /// Like [`NotificationGate::wait`], but gives up once `cancelled` returns true. Returns
+    /// whether the notification may go out.
+    pub fn wait_unless<F: Fn() -> bool>(&self, version: BuildVersion, cancelled: F) -> bool {
+        while self.check(version).is_err() {
+            if cancelled() {
+                return false;
+            }
+            self.clock.sleep(RECHECK);
+        }
+        !cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration as StdDuration;
+    use chrono::{Duration, FixedOffset, NaiveTime, TimeZone, Utc, Weekday};
+    use crate::build_version::BuildVersion;
+    use crate::clock::Clock;
+    use crate::gate::{NotificationGate, Suppressed};
+    use crate::notification::NotificationConfig;
+    use crate::notifier::{Notifier, NotifierHub};
+    use crate::release::DetectedRelease;
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::state_store::SqliteStateStore;
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater, ManualClock};
+
+    fn config() -> NotificationConfig {
+        let config: NotificationConfig = toml::from_str(r#"
+            quiet_hours = { start = "22:00", end = "08:00" }
+            quiet_days = ["Sun"]
+        "#).unwrap();
+        assert_eq!(config.quiet_hours.unwrap().start, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+        config
+    }
+
+    fn gate() -> (Arc<ManualClock>, NotificationGate) {
+        // Friday 20:00 at UTC+8.
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 7, 5, 12, 0, 0).unwrap()));
+        let gate = NotificationGate::new(&config()).with_clock(clock.clone(), FixedOffset::east_opt(8 * 3600).unwrap());
+        (clock, gate)
+    }
+
+    #[test]
+    fn holds_back_during_quiet_hours_days_and_do_not_disturb() {
+        let (clock, gate) = gate();
+        let version = BuildVersion::parse("R1.02.03T04").unwrap();
+        assert_eq!(gate.check(version), Ok(()));
+
+        clock.advance(StdDuration::from_secs(2 * 3600));
+        assert_eq!(gate.check(version), Err(Suppressed::QuietHours));
+        clock.advance(StdDuration::from_secs(10 * 3600));
+        assert_eq!(gate.check(version), Ok(()));
+        clock.advance(StdDuration::from_secs(24 * 3600));
+        assert_eq!(gate.check(version), Err(Suppressed::QuietDay(Weekday::Sun)));
+
+        gate.configure(&NotificationConfig::default());
+        assert_eq!(gate.check(version), Ok(()));
+        gate.set_do_not_disturb(true);
+        assert_eq!(gate.check(version), Err(Suppressed::DoNotDisturb));
+    }
+
+    #[test]
+    fn snoozes_one_version_until_it_expires() {
+        let (clock, gate) = gate();
+        let snoozed = BuildVersion::parse("R1.02.03T04").unwrap();
+        let until = gate.snooze(snoozed, Duration::minutes(30));
+
+        assert_eq!(gate.check(snoozed), Err(Suppressed::Snoozed(until)));
+        assert_eq!(gate.check(BuildVersion::parse("R1.02.03T05").unwrap()), Ok(()));
+        clock.advance(StdDuration::from_secs(30 * 60));
+        assert_eq!(gate.check(snoozed), Ok(()));
+    }
+
+    struct Recorder {
+        sent: Mutex<mpsc::Sender<BuildVersion>>,
+        waits_for_gate: bool
+    }
+
+    impl Notifier for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error> {
+            self.sent.lock().unwrap().send(release.version)?;
+            Ok(())
+        }
+
+        fn waits_for_gate(&self) -> bool {
+            self.waits_for_gate
+        }
+    }
+
+    fn recorder(waits_for_gate: bool) -> (Arc<Recorder>, mpsc::Receiver<BuildVersion>) {
+        let (tx, rx) = mpsc::channel();
+        (Arc::new(Recorder { sent: Mutex::new(tx), waits_for_gate }), rx)
+    }
+
+    #[test]
+    fn notifiers_wait_for_the_gate_to_open() {
+        let (clock, gate) = gate();
+        let checker = Arc::new(FakeVersionChecker::default());
+        let updater = Arc::new(FakeVersionUpdater::new("a"));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        let monitor = ReleaseMonitor::new("a", checker.clone(), updater, store, 60);
+        let (person, to_person) = recorder(true);
+        let (download, to_download) = recorder(false);
+        let hub = NotifierHub::new(vec![person, download]).with_gate(Arc::new(gate));
+        let _handles = hub.attach(&monitor);
+
+        // Found during quiet hours.
+        clock.advance(StdDuration::from_secs(3 * 3600));
+        checker.set_version(BuildVersion::parse("R1.02.03T04").unwrap());
+        monitor.scan_once(clock.now()).unwrap();
+
+        assert_eq!(to_download.recv_timeout(StdDuration::from_secs(5)), Ok(BuildVersion::parse("R1.02.03T04").unwrap()));
+        clock.wait_for_sleeper();
+        assert!(to_person.try_recv().is_err());
+
+        // Sent once quiet hours are over.
+        clock.advance(StdDuration::from_secs(9 * 3600));
+        assert_eq!(to_person.recv_timeout(StdDuration::from_secs(5)), Ok(BuildVersion::parse("R1.02.03T04").unwrap()));
+    }
+
+    #[test]
+    fn do_not_disturb_survives_a_restart() {
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        NotificationGate::new(&NotificationConfig::default()).with_store(store.clone()).set_do_not_disturb(true);
+        assert!(NotificationGate::new(&NotificationConfig::default()).with_store(store).do_not_disturb());
+    }
+}
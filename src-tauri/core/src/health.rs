@@ -0,0 +1,114 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// The source was reached but the last scan failed.
+    Degraded,
+    Unreachable
+}
+
+/// How a source's scans have been going.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SourceHealth {
+    pub source: String,
+    pub status: HealthStatus,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>
+}
+
+impl SourceHealth {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.into(),
+            status: HealthStatus::Ok,
+            consecutive_failures: 0,
+            last_error: None,
+            last_success: None,
+            last_failure: None
+        }
+    }
+
+    /// Records a successful scan. Returns whether the status changed.
+    pub fn record_success(&mut self, now: DateTime<Utc>) -> bool {
+        let changed = self.status != HealthStatus::Ok;
+        self.status = HealthStatus::Ok;
+        self.consecutive_failures = 0;
+        self.last_success = Some(now);
+        changed
+    }
+
+    /// Records a failed scan. Returns whether the status changed.
+    pub fn record_failure(&mut self, error: &str, unreachable: bool, now: DateTime<Utc>) -> bool {
+        let status = if unreachable { HealthStatus::Unreachable } else { HealthStatus::Degraded };
+        let changed = self.status != status;
+        self.status = status;
+        self.consecutive_failures += 1;
+        self.last_error = Some(error.into());
+        self.last_failure = Some(now);
+        changed
+    }
+}
+
+/// Exponential backoff between retries of a failing source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { initial: Duration::from_secs(5), max: Duration::from_secs(15 * 60) }
+    }
+}
+
+impl Backoff {
+    /// The wait before the next attempt after `failures` consecutive failures.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use chrono::Utc;
+    use crate::health::{Backoff, HealthStatus, SourceHealth};
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let backoff = Backoff { initial: Duration::from_secs(5), max: Duration::from_secs(60) };
+        assert_eq!(backoff.delay(1), Duration::from_secs(5));
+        assert_eq!(backoff.delay(2), Duration::from_secs(10));
+        assert_eq!(backoff.delay(4), Duration::from_secs(40));
+        assert_eq!(backoff.delay(5), Duration::from_secs(60));
+        assert_eq!(backoff.delay(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn tracks_failures_until_the_next_success() {
+        let mut health = SourceHealth::new("a");
+        let now = Utc::now();
+
+        assert!(health.record_failure("gone", true, now));
+        assert!(!health.record_failure("still gone", true, now));
+        assert_eq!(health.status, HealthStatus::Unreachable);
+        assert_eq!(health.consecutive_failures, 2);
+        assert_eq!(health.last_error.as_deref(), Some("still gone"));
+
+        assert!(health.record_failure("bad listing", false, now));
+        assert_eq!(health.status, HealthStatus::Degraded);
+
+        assert!(health.record_success(now));
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.last_success, Some(now));
+        assert_eq!(health.last_error.as_deref(), Some("bad listing"));
+    }
+}
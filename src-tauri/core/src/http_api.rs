@@ -0,0 +1,277 @@
+//! An opt-in HTTP API on the loopback interface for dashboards and bots.
+//!
+//! * `GET /status`
+//! * `GET /sources/{id}/latest`
+//! * `POST /ack` with `{"version": "R1.02.03T04", "note": "..."}`
+//! * `GET /events`, a server-sent-events stream of everything the monitor publishes
+//! * `GET /metrics`, in the OpenMetrics text format
+//!
+//! Every request needs `Authorization: Bearer <token>`. `/events` also takes `?token=<token>` for
+//! clients like `EventSource` which can't set headers; elsewhere it would only end up in logs.
+
+use std::io::Write;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use anyhow::anyhow;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info};
+use crate::metrics;
+use crate::publisher::{Event, Message};
+use crate::services::AppServices;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Deserialize)]
+struct AckBody {
+    version: String,
+    #[serde(default)]
+    note: Option<String>
+}
+
+/// The running server. Dropping it stops accepting requests.
+pub struct HttpApi {
+    server: Arc<Server>,
+    port: u16
+}
+
+impl HttpApi {
+    /// Listens on `127.0.0.1:port`. Port 0 picks a free one.
+    pub fn start(port: u16, token: &str, services: AppServices) -> Result<HttpApi, anyhow::Error> {
+        if token.is_empty() {
+            return Err(anyhow!("The HTTP API needs a token."));
+        }
+
+        let server = Arc::new(Server::http(("127.0.0.1", port)).map_err(|e| anyhow!(e))?);
+        let port = server.server_addr().to_ip().map(|a| a.port()).unwrap_or(port);
+        info!("HTTP API listening on 127.0.0.1:{}.", port);
+
+        let s = server.clone();
+        let token = token.to_string();
+        thread::spawn(move || {
+            for request in s.incoming_requests() {
+                let services = services.clone();
+                let token = token.clone();
+                thread::spawn(move || handle(&services, &token, request));
+            }
+        });
+
+        Ok(HttpApi { server, port })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for HttpApi {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// Looks at every byte, so how long a comparison takes doesn't give the token away.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn authorized(request: &Request, token: &str, allow_query: bool) -> bool {
+    let header = request.headers().iter()
+        .filter(|h| h.field.equiv("Authorization"))
+        .filter_map(|h| h.value.as_str().strip_prefix("Bearer "))
+        .any(|t| same_token(t, token));
+    let query = allow_query && request.url().split_once('?')
+        .map(|(_, q)| q.split('&').filter_map(|p| p.strip_prefix("token=")).any(|t| same_token(t, token)))
+        .unwrap_or(false);
+    header || query
+}
+
+fn json_response(status: u16, body: &Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    Response::from_string(body.to_string()).with_status_code(status).with_header(content_type)
+}
+
+/// Answers everything except the event stream.
+fn route(services: &AppServices, method: &Method, path: &str, body: &str) -> (u16, Value) {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["status"]) => (200, json!(services.status())),
+        (Method::Get, ["sources", id, "latest"]) => {
+            if *id != services.release_monitor.source() {
+                return (404, json!({ "error": format!("Unknown source {}.", id) }));
+            }
+            (200, json!({ "source": id, "version": services.release_monitor.last_seen(), "release": services.latest_release() }))
+        }
+        (Method::Post, ["ack"]) => {
+            let ack = match serde_json::from_str::<AckBody>(body) {
+                Ok(a) => a,
+                Err(e) => return (400, json!({ "error": e.to_string() }))
+            };
+            if !services.acknowledge(&ack.version, ack.note) {
+                return (400, json!({ "error": format!("{} is not a version.", ack.version) }));
+            }
+            (200, json!({ "acknowledged": services.version_updater.get_version() }))
+        }
+        _ => (404, json!({ "error": "Not found." }))
+    }
+}
+
+fn handle(services: &AppServices, token: &str, mut request: Request) {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    let events = *request.method() == Method::Get && path == "/events";
+    if !authorized(&request, token, events) {
+        let _ = request.respond(json_response(401, &json!({ "error": "Unauthorized." })));
+        return;
+    }
+
+    if events {
+        stream_events(services, request);
+        return;
+    }
+
+    if *request.method() == Method::Get && path == "/metrics" {
+        let content_type = Header::from_bytes(&b"Content-Type"[..], metrics::CONTENT_TYPE.as_bytes()).unwrap();
+        let response = Response::from_string(metrics::render(services, Utc::now())).with_header(content_type);
+        let _ = request.respond(response);
+        return;
+    }
+
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        let _ = request.respond(json_response(400, &json!({ "error": e.to_string() })));
+        return;
+    }
+
+    let (status, value) = route(services, request.method(), &path, &body);
+    match request.respond(json_response(status, &value)) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Unable to respond to {}! Error: {}", path, e);
+        }
+    }
+}
+
+/// Writes events until the client goes away. The response is written by hand so every event is
+/// flushed as it happens rather than buffered into chunks.
+fn stream_events(services: &AppServices, request: Request) {
+    let (tx, rx) = mpsc::channel::<Message>();
+    let tx = Mutex::new(tx);
+    let _handle = services.release_monitor.subscribe_many(&Event::ALL, move |m| {
+        let _ = tx.lock().unwrap().send(m.clone());
+    });
+
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    if writer.write_all(head.as_bytes()).and_then(|_| writer.flush()).is_err() {
+        return;
+    }
+
+    loop {
+        let chunk = match rx.recv_timeout(KEEP_ALIVE) {
+            Ok(m) => {
+                let data = serde_json::to_value(&m).unwrap_or_default();
+                let kind = data["kind"].as_str().unwrap_or("message").to_string();
+                format!("event: {}\ndata: {}\n\n", kind, data)
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => String::from(": keep-alive\n\n"),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break
+        };
+        if writer.write_all(chunk.as_bytes()).and_then(|_| writer.flush()).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::sync::Arc;
+    use serde_json::Value;
+    use crate::build_version::BuildVersion;
+    use crate::http_api::HttpApi;
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::services::AppServices;
+    use crate::state_store::{DetectionStore, SqliteStateStore};
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater};
+
+    const TOKEN: &str = "secret";
+
+    fn start() -> (AppServices, HttpApi) {
+        let checker = Arc::new(FakeVersionChecker::default());
+        let updater = Arc::new(FakeVersionUpdater::new("a"));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        store.record_seen(BuildVersion::parse("R1.02.03T04").unwrap());
+        let release_monitor = Arc::new(ReleaseMonitor::new("a", checker.clone(), updater.clone(), store, 60));
+        let services = AppServices { release_monitor, version_checker: checker, version_updater: updater };
+        let api = HttpApi::start(0, TOKEN, services.clone()).unwrap();
+        (services, api)
+    }
+
+    fn url(api: &HttpApi, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", api.port(), path)
+    }
+
+    fn json(response: ureq::Response) -> Value {
+        serde_json::from_str(&response.into_string().unwrap()).unwrap()
+    }
+
+    fn status_of(result: Result<ureq::Response, ureq::Error>) -> u16 {
+        match result {
+            Ok(r) => r.status(),
+            Err(ureq::Error::Status(code, _)) => code,
+            Err(e) => panic!("Request failed: {}", e)
+        }
+    }
+
+    #[test]
+    fn requires_the_token() {
+        let (_, api) = start();
+        assert_eq!(status_of(ureq::get(&url(&api, "/status")).call()), 401);
+        assert_eq!(status_of(ureq::get(&url(&api, "/status")).set("Authorization", "Bearer wrong").call()), 401);
+        assert_eq!(status_of(ureq::get(&url(&api, "/status")).set("Authorization", "Bearer secres").call()), 401);
+        assert_eq!(status_of(ureq::get(&url(&api, "/status")).set("Authorization", "Bearer secret").call()), 200);
+        assert_eq!(status_of(ureq::get(&url(&api, "/status?token=secret")).call()), 401);
+        assert!(HttpApi::start(0, "", start().0).is_err());
+    }
+
+    #[test]
+    fn serves_status_and_acknowledges() {
+        let (_, api) = start();
+        let auth = format!("Bearer {}", TOKEN);
+
+        let status = json(ureq::get(&url(&api, "/status")).set("Authorization", &auth).call().unwrap());
+        assert_eq!(status["latest"], "R1.02.03T04");
+        assert_eq!(status["acknowledged"], "R0.00.00T00");
+
+        let latest = json(ureq::get(&url(&api, "/sources/a/latest")).set("Authorization", &auth).call().unwrap());
+        assert_eq!(latest["version"], "R1.02.03T04");
+        assert_eq!(status_of(ureq::get(&url(&api, "/sources/b/latest")).set("Authorization", &auth).call()), 404);
+
+        let bad = ureq::post(&url(&api, "/ack")).set("Authorization", &auth).send_string(r#"{"version": "nope"}"#);
+        assert_eq!(status_of(bad), 400);
+        let acked = json(ureq::post(&url(&api, "/ack")).set("Authorization", &auth)
+            .send_string(r#"{"version": "R1.02.03T04", "note": "from a bot"}"#).unwrap());
+        assert_eq!(acked["acknowledged"], "R1.02.03T04");
+
+        let metrics = ureq::get(&url(&api, "/metrics")).set("Authorization", &auth).call().unwrap();
+        assert!(metrics.content_type().starts_with("application/openmetrics-text"));
+        assert!(metrics.into_string().unwrap().contains("release_monitor_unacknowledged{source=\"a\"} 0"));
+    }
+
+    #[test]
+    fn streams_events() {
+        let (services, api) = start();
+        let response = ureq::get(&url(&api, "/events?token=secret")).call().unwrap();
+        assert_eq!(response.content_type(), "text/event-stream");
+
+        services.acknowledge("R1.02.03T04", None);
+
+        let mut lines = BufReader::new(response.into_reader()).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "event: version_acknowledged");
+        let data: Value = serde_json::from_str(lines.next().unwrap().unwrap().trim_start_matches("data: ")).unwrap();
+        assert_eq!(data["payload"]["version"], "R1.02.03T04");
+    }
+}
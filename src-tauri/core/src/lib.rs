@@ -0,0 +1,33 @@
+//! Everything the release monitor does that doesn't need a window: checking sources, keeping
+//! track of acknowledgements, publishing events and the command line.
+
+pub mod ack_history;
+pub mod build_version;
+pub mod chat;
+pub mod cli;
+pub mod clock;
+pub mod command_hook;
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod download;
+pub mod email;
+pub mod gate;
+pub mod health;
+pub mod http_api;
+pub mod metrics;
+pub mod notification;
+pub mod notifier;
+pub mod publisher;
+pub mod release;
+pub mod release_monitor;
+pub mod services;
+pub mod source_state;
+pub mod state_store;
+pub mod statistics;
+pub mod template;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod version_checker;
+pub mod version_updater;
+pub mod webhook;
@@ -0,0 +1,128 @@
+use std::fmt::Write;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::build_version::BuildVersion;
+use crate::health::HealthStatus;
+use crate::services::AppServices;
+use crate::version_checker::UnreachableSourceError;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Counters kept by the scan loop.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScanMetrics {
+    pub scans: u64,
+    pub unreachable_errors: u64,
+    pub failed_errors: u64,
+    pub duration_seconds_sum: f64,
+    pub last_duration_seconds: f64
+}
+
+impl ScanMetrics {
+    pub fn record(&mut self, duration: Duration, error: Option<&anyhow::Error>) {
+        self.scans += 1;
+        self.duration_seconds_sum += duration.as_secs_f64();
+        self.last_duration_seconds = duration.as_secs_f64();
+        match error {
+            None => {}
+            Some(e) if e.downcast_ref::<UnreachableSourceError>().is_some() => self.unreachable_errors += 1,
+            Some(_) => self.failed_errors += 1
+        }
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+/// Renders the monitor's metrics in the OpenMetrics text format.
+pub fn render(services: &AppServices, now: DateTime<Utc>) -> String {
+    let monitor = &services.release_monitor;
+    let source = escape(monitor.source());
+    let scans = monitor.scan_metrics();
+    let latest = monitor.last_seen();
+    let acknowledged = services.version_updater.get_version();
+    let last_new_build = monitor.release_history().last().map(|e| e.first_seen);
+
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        for (suffix_and_labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, suffix_and_labels, value);
+        }
+    };
+
+    let labels = format!("{{source=\"{}\"}}", source);
+    metric("release_monitor_scans", "counter", "Scans of the source.",
+           &[(format!("_total{}", labels), scans.scans.to_string())]);
+    metric("release_monitor_scan_errors", "counter", "Failed scans of the source.",
+           &[(format!("_total{{source=\"{}\",kind=\"unreachable\"}}", source), scans.unreachable_errors.to_string()),
+             (format!("_total{{source=\"{}\",kind=\"failed\"}}", source), scans.failed_errors.to_string())]);
+    metric("release_monitor_scan_duration_seconds", "summary", "Time taken by scans.",
+           &[(format!("_sum{}", labels), scans.duration_seconds_sum.to_string()),
+             (format!("_count{}", labels), scans.scans.to_string())]);
+    metric("release_monitor_last_scan_duration_seconds", "gauge", "Time taken by the last scan.",
+           &[(labels.clone(), scans.last_duration_seconds.to_string())]);
+
+    let components = [("major", latest.major), ("minor", latest.minor), ("patch", latest.patch), ("t", latest.t)];
+    let samples: Vec<(String, String)> = components.iter()
+        .map(|(c, v)| (format!("{{source=\"{}\",component=\"{}\"}}", source, c), v.to_string()))
+        .collect();
+    metric("release_monitor_latest_version", "gauge", "Components of the latest version seen.", &samples);
+
+    if let Some(first_seen) = last_new_build {
+        metric("release_monitor_seconds_since_last_new_build", "gauge", "Time since a new version was last seen.",
+               &[(labels.clone(), (now - first_seen).num_seconds().to_string())]);
+    }
+
+    let unacknowledged = latest != BuildVersion::default() && latest != acknowledged;
+    metric("release_monitor_unacknowledged", "gauge", "Whether the latest version is unacknowledged.",
+           &[(labels.clone(), (unacknowledged as u8).to_string())]);
+    let reachable = monitor.health().status != HealthStatus::Unreachable;
+    metric("release_monitor_source_reachable", "gauge", "Whether the source could be reached on the last scan.",
+           &[(labels.clone(), (reachable as u8).to_string())]);
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use chrono::{Duration, Utc};
+    use crate::build_version::BuildVersion;
+    use crate::metrics::render;
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::services::AppServices;
+    use crate::state_store::SqliteStateStore;
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater};
+
+    #[test]
+    fn renders_scan_counters_and_gauges() {
+        let checker = Arc::new(FakeVersionChecker::default());
+        let updater = Arc::new(FakeVersionUpdater::new("a \"b\""));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a \"b\"").unwrap());
+        let release_monitor = Arc::new(ReleaseMonitor::new("a \"b\"", checker.clone(), updater.clone(), store, 60));
+        let services = AppServices { release_monitor: release_monitor.clone(), version_checker: checker.clone(), version_updater: updater };
+
+        checker.unreachable("//share/builds");
+        let _ = release_monitor.scan_once(Utc::now());
+        checker.fail("bad listing");
+        let _ = release_monitor.scan_once(Utc::now());
+        checker.set_version(BuildVersion::parse("R1.02.03T04").unwrap());
+        release_monitor.scan_once(Utc::now()).unwrap();
+
+        let text = render(&services, Utc::now() + Duration::seconds(90));
+        assert!(text.contains("release_monitor_scans_total{source=\"a \\\"b\\\"\"} 3\n"));
+        assert!(text.contains("release_monitor_scan_errors_total{source=\"a \\\"b\\\"\",kind=\"unreachable\"} 1\n"));
+        assert!(text.contains("release_monitor_scan_errors_total{source=\"a \\\"b\\\"\",kind=\"failed\"} 1\n"));
+        assert!(text.contains("release_monitor_scan_duration_seconds_count{source=\"a \\\"b\\\"\"} 3\n"));
+        assert!(text.contains("release_monitor_latest_version{source=\"a \\\"b\\\"\",component=\"minor\"} 2\n"));
+        assert!(text.contains("release_monitor_seconds_since_last_new_build{source=\"a \\\"b\\\"\"} 90\n"));
+        assert!(text.contains("release_monitor_unacknowledged{source=\"a \\\"b\\\"\"} 1\n"));
+        assert!(text.contains("release_monitor_source_reachable{source=\"a \\\"b\\\"\"} 1\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,371 @@
+//! Text and actions for the desktop notification shown on new versions and reminders.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use anyhow::anyhow;
+use chrono::{Duration, Weekday};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use crate::build_version::BuildVersion;
+use crate::gate::{NotificationGate, QuietHours};
+use crate::release::DetectedRelease;
+use crate::services::AppServices;
+use crate::template;
+
+pub const DEFAULT_LOCALE: &str = "en-SG";
+
+fn default_snooze_minutes() -> u32 {
+    60
+}
+
+/// ```toml
+/// [notification]
+/// locale = "en"
+/// title = "{version} is out"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NotificationConfig {
+    /// Picks the built-in text, `en-SG` or `en`.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Replace the locale's text. Besides the usual `{version}`, `{source}`, `{path}`, `{name}`
+    /// and `{size}`, reminders have `{count}`.
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub reminder_title: Option<String>,
+    #[serde(default)]
+    pub reminder_body: Option<String>,
+    /// How long the snooze action silences a version.
+    #[serde(default = "default_snooze_minutes")]
+    pub snooze_minutes: u32,
+    /// No notifications between these local times, e.g. `{ start = "22:00", end = "08:00" }`.
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// No notifications at all on these days, e.g. `["Sat", "Sun"]`.
+    #[serde(default)]
+    pub quiet_days: Vec<Weekday>
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            locale: None,
+            title: None,
+            body: None,
+            reminder_title: None,
+            reminder_body: None,
+            snooze_minutes: default_snooze_minutes(),
+            quiet_hours: None,
+            quiet_days: Vec::new()
+        }
+    }
+}
+
+struct Strings {
+    title: &'static str,
+    body: &'static str,
+    reminder_title: &'static str,
+    reminder_body: &'static str,
+    acknowledge: &'static str,
+    snooze: &'static str,
+    open_folder: &'static str
+}
+
+fn strings(locale: &str) -> Strings {
+    match locale {
+        "en" => Strings {
+            title: "New build available",
+            body: "{version} is ready to install.",
+            reminder_title: "{version} is still not installed",
+            reminder_body: "Reminder {count}: {version} on {source} is waiting to be installed.",
+            acknowledge: "Acknowledge",
+            snooze: "Snooze {snooze}",
+            open_folder: "Open folder"
+        },
+        _ => Strings {
+            title: "Aiyoyo! Got new build version!",
+            body: "Mai tu liao! Must install {version} right now!",
+            reminder_title: "Oi! {version} still never install ah?",
+            reminder_body: "Told you {count} times already leh! Go install {version} now!",
+            acknowledge: "Know already",
+            snooze: "Later lah ({snooze})",
+            open_folder: "Open folder"
+        }
+    }
+}
+
+/// What the user can do straight from the notification.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NotificationAction {
+    Acknowledge,
+    Snooze,
+    OpenFolder
+}
+
+impl NotificationAction {
+    pub fn id(&self) -> &'static str {
+        match self {
+            NotificationAction::Acknowledge => "acknowledge",
+            NotificationAction::Snooze => "snooze",
+            NotificationAction::OpenFolder => "open_folder"
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "acknowledge" => Some(NotificationAction::Acknowledge),
+            "snooze" => Some(NotificationAction::Snooze),
+            "open_folder" => Some(NotificationAction::OpenFolder),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationText {
+    pub title: String,
+    pub body: String,
+    /// Action buttons and their labels. Opening the folder is only offered when the file is known.
+    pub actions: Vec<(NotificationAction, String)>
+}
+
+fn duration_label(minutes: u32) -> String {
+    if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// The notification for a new version, or for a reminder when `reminder` has the count.
+pub fn text(config: &NotificationConfig, release: &DetectedRelease, reminder: Option<u32>) -> NotificationText {
+    let strings = strings(config.locale.as_deref().unwrap_or(DEFAULT_LOCALE));
+    let mut vars = release.vars();
+    vars.push(("count", reminder.unwrap_or_default().to_string()));
+    vars.push(("snooze", duration_label(config.snooze_minutes)));
+
+    let (title, body) = match reminder {
+        None => (config.title.as_deref().unwrap_or(strings.title), config.body.as_deref().unwrap_or(strings.body)),
+        Some(_) => (config.reminder_title.as_deref().unwrap_or(strings.reminder_title),
+                    config.reminder_body.as_deref().unwrap_or(strings.reminder_body))
+    };
+
+    let mut actions = vec![
+        (NotificationAction::Acknowledge, strings.acknowledge.to_string()),
+        (NotificationAction::Snooze, template::render(strings.snooze, &vars))
+    ];
+    if release.path.is_some() {
+        actions.push((NotificationAction::OpenFolder, strings.open_folder.to_string()));
+    }
+
+    NotificationText { title: template::render(title, &vars), body: template::render(body, &vars), actions }
+}
+
+/// Carries out an action picked on the notification for `release`.
+pub fn perform(action: NotificationAction,
+               services: &AppServices,
+               gate: &NotificationGate,
+               config: &NotificationConfig,
+               release: &DetectedRelease) -> Result<(), anyhow::Error> {
+    match action {
+        NotificationAction::Acknowledge => {
+            if !services.acknowledge(&release.version.to_string(), None) {
+                return Err(anyhow!("Unable to acknowledge {}.", release.version));
+            }
+            Ok(())
+        }
+        NotificationAction::Snooze => {
+            gate.snooze(release.version, Duration::minutes(config.snooze_minutes as i64));
+            Ok(())
+        }
+        NotificationAction::OpenFolder => release.reveal()
+    }
+}
+
+/// Shows a desktop notification and hands the action picked on it to the callback.
+pub type ShowNotification = dyn Fn(NotificationText, Box<dyn FnOnce(NotificationAction) + Send>) + Send + Sync;
+
+/// Shows the desktop notification about new versions and reminders. Ones the gate holds back are
+/// shown once it opens, and a snoozed version comes back when the snooze ends.
+#[derive(Clone)]
+pub struct DesktopNotifier {
+    services: AppServices,
+    gate: Arc<NotificationGate>,
+    config: Arc<NotificationConfig>,
+    show: Arc<ShowNotification>,
+    /// Versions with a notification waiting for the gate, so reminders don't pile up behind it.
+    waiting: Arc<Mutex<HashSet<BuildVersion>>>
+}
+
+impl DesktopNotifier {
+    pub fn new(services: AppServices, gate: Arc<NotificationGate>, config: NotificationConfig, show: Arc<ShowNotification>) -> Self {
+        Self { services, gate, config: Arc::new(config), show, waiting: Arc::new(Mutex::new(HashSet::new())) }
+    }
+
+    /// Shows the notification about `release` once the gate lets it through, unless the version
+    /// is acknowledged first. Doesn't block.
+    pub fn notify(&self, release: DetectedRelease, reminder: Option<u32>) {
+        let version = release.version;
+        if let Err(reason) = self.gate.check(version) {
+            if !self.waiting.lock().unwrap().insert(version) {
+                return;
+            }
+            info!("Holding back the notification about {} because {}.", version, reason);
+        }
+
+        let notifier = self.clone();
+        thread::spawn(move || {
+            let acked = || notifier.services.is_acked(&version.to_string());
+            let open = notifier.gate.wait_unless(version, acked);
+            notifier.waiting.lock().unwrap().remove(&version);
+            if !open {
+                info!("Not notifying about {} because it was acknowledged.", version);
+                return;
+            }
+
+            let text = text(&notifier.config, &release, reminder);
+            let on_action = {
+                let notifier = notifier.clone();
+                move |action| notifier.perform(action, release, reminder)
+            };
+            (notifier.show)(text, Box::new(on_action));
+        });
+    }
+
+    fn perform(&self, action: NotificationAction, release: DetectedRelease, reminder: Option<u32>) {
+        match perform(action, &self.services, &self.gate, &self.config, &release) {
+            Ok(_) => {}
+            Err(e) => {
+                error!("Unable to {} from notification! Error: {}", action.id(), e);
+            }
+        }
+        if action == NotificationAction::Snooze {
+            self.notify(release, reminder);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration as StdDuration;
+    use chrono::{Duration, FixedOffset, TimeZone, Utc};
+    use crate::build_version::BuildVersion;
+    use crate::gate::{NotificationGate, Suppressed};
+    use crate::notification::{perform, text, DesktopNotifier, NotificationAction, NotificationConfig, NotificationText};
+    use crate::release::DetectedRelease;
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::services::AppServices;
+    use crate::state_store::SqliteStateStore;
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater, ManualClock};
+
+    fn release() -> DetectedRelease {
+        DetectedRelease::new("a", BuildVersion::parse("R1.02.03T04").unwrap())
+    }
+
+    #[test]
+    fn renders_the_locale_or_configured_text() {
+        let default = text(&NotificationConfig::default(), &release(), None);
+        assert_eq!(default.body, "Mai tu liao! Must install R1.02.03T04 right now!");
+        assert_eq!(default.actions[1].1, "Later lah (1h)");
+        assert_eq!(default.actions.len(), 2);
+
+        let config = NotificationConfig {
+            locale: Some(String::from("en")),
+            reminder_body: Some(String::from("{version} reminder {count}")),
+            snooze_minutes: 30,
+            ..Default::default()
+        };
+        let with_path = DetectedRelease { path: Some(String::from("/share/R1.02.03T04.zip")), ..release() };
+        let reminder = text(&config, &with_path, Some(3));
+        assert_eq!(reminder.title, "R1.02.03T04 is still not installed");
+        assert_eq!(reminder.body, "R1.02.03T04 reminder 3");
+        assert_eq!(reminder.actions.iter().map(|(a, l)| (a.id(), l.as_str())).collect::<Vec<_>>(),
+                   vec![("acknowledge", "Acknowledge"), ("snooze", "Snooze 30m"), ("open_folder", "Open folder")]);
+    }
+
+    fn services() -> AppServices {
+        let checker = Arc::new(FakeVersionChecker::default());
+        let updater = Arc::new(FakeVersionUpdater::new("a"));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        let release_monitor = Arc::new(ReleaseMonitor::new("a", checker.clone(), updater.clone(), store, 60));
+        AppServices { release_monitor, version_checker: checker, version_updater: updater }
+    }
+
+    type Shown = (String, Box<dyn FnOnce(NotificationAction) + Send>);
+
+    /// A notifier whose gate runs on a manual clock, starting on a Friday 20:00 at UTC+8, with
+    /// quiet hours from 22:00.
+    fn desktop(services: &AppServices) -> (Arc<ManualClock>, DesktopNotifier, mpsc::Receiver<Shown>) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 7, 5, 12, 0, 0).unwrap()));
+        let config: NotificationConfig = toml::from_str(r#"quiet_hours = { start = "22:00", end = "08:00" }"#).unwrap();
+        let gate = NotificationGate::new(&config).with_clock(clock.clone(), FixedOffset::east_opt(8 * 3600).unwrap());
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let show = Arc::new(move |text: NotificationText, on_action: Box<dyn FnOnce(NotificationAction) + Send>| {
+            let _ = tx.lock().unwrap().send((text.body, on_action));
+        });
+        (clock, DesktopNotifier::new(services.clone(), Arc::new(gate), config, show), rx)
+    }
+
+    #[test]
+    fn held_back_notifications_are_shown_once_the_gate_opens() {
+        let services = services();
+        let (clock, desktop, shown) = desktop(&services);
+
+        // Found during quiet hours. Reminders don't queue up behind it.
+        clock.advance(StdDuration::from_secs(3 * 3600));
+        desktop.notify(release(), None);
+        desktop.notify(release(), Some(1));
+        clock.wait_for_sleeper();
+        assert!(shown.try_recv().is_err());
+
+        clock.advance(StdDuration::from_secs(9 * 3600));
+        assert_eq!(shown.recv_timeout(StdDuration::from_secs(5)).unwrap().0, "Mai tu liao! Must install R1.02.03T04 right now!");
+        assert!(shown.recv_timeout(StdDuration::from_millis(100)).is_err());
+
+        // Acknowledged while held back.
+        clock.advance(StdDuration::from_secs(14 * 3600));
+        desktop.notify(release(), None);
+        clock.wait_for_sleeper();
+        assert!(services.acknowledge("R1.02.03T04", None));
+        clock.advance(StdDuration::from_secs(10 * 3600));
+        assert!(shown.recv_timeout(StdDuration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn snoozed_notifications_come_back_when_the_snooze_ends() {
+        let services = services();
+        let (clock, desktop, shown) = desktop(&services);
+
+        desktop.notify(release(), None);
+        let (_, on_action) = shown.recv_timeout(StdDuration::from_secs(5)).unwrap();
+        on_action(NotificationAction::Snooze);
+        clock.wait_for_sleeper();
+        assert!(shown.try_recv().is_err());
+
+        clock.advance(StdDuration::from_secs(60 * 60));
+        assert!(shown.recv_timeout(StdDuration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn actions_acknowledge_and_snooze() {
+        let services = services();
+        let config = NotificationConfig::default();
+        let gate = NotificationGate::new(&config);
+
+        perform(NotificationAction::Snooze, &services, &gate, &config, &release()).unwrap();
+        match gate.check(release().version) {
+            Err(Suppressed::Snoozed(until)) => assert!(until > Utc::now() + Duration::minutes(59)),
+            other => panic!("Expected a snooze, got {:?}", other)
+        }
+
+        perform(NotificationAction::Acknowledge, &services, &gate, &config, &release()).unwrap();
+        assert!(services.is_acked("R1.02.03T04"));
+        assert!(perform(NotificationAction::OpenFolder, &services, &gate, &config, &release()).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use tracing::{error, info};
+use crate::gate::NotificationGate;
+use crate::publisher::{Event, Message, SubscriptionHandle};
+use crate::release::DetectedRelease;
+use crate::release_monitor::ReleaseMonitor;
+
+/// Tells someone about a new version.
+pub trait Notifier {
+    fn name(&self) -> &str;
+    fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error>;
+
+    /// Whether this notifier tells people, and so waits out snoozes, quiet hours and
+    /// do-not-disturb. Ones which only prepare the build for installing don't.
+    fn waits_for_gate(&self) -> bool {
+        true
+    }
+}
+
+/// Fans new versions out to every configured notifier. Those which tell people wait while the
+/// gate holds them back and send once it opens.
+#[derive(Default)]
+pub struct NotifierHub {
+    notifiers: Vec<Arc<dyn Notifier + Send + Sync>>,
+    gate: Option<Arc<NotificationGate>>
+}
+
+impl NotifierHub {
+    pub fn new(notifiers: Vec<Arc<dyn Notifier + Send + Sync>>) -> Self {
+        Self { notifiers, gate: None }
+    }
+
+    pub fn with_gate(mut self, gate: Arc<NotificationGate>) -> Self {
+        self.gate = Some(gate);
+        self
+    }
+
+    pub fn add(&mut self, notifier: Arc<dyn Notifier + Send + Sync>) {
+        self.notifiers.push(notifier);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+
+    /// Subscribes every notifier to the monitor's detections. Each gets its own subscription so a
+    /// slow or retrying notifier doesn't hold up the others.
+    pub fn attach(&self, monitor: &ReleaseMonitor) -> Vec<SubscriptionHandle> {
+        self.notifiers.iter().map(|n| {
+            let notifier = n.clone();
+            let gate = self.gate.clone();
+            monitor.subscribe(Event::VersionDetected, move |m| {
+                let release = match m {
+                    Message::VersionDetected(d) => &d.release,
+                    _ => return
+                };
+                if let Some(gate) = gate.as_ref().filter(|_| notifier.waits_for_gate()) {
+                    if let Err(reason) = gate.check(release.version) {
+                        info!("Holding back {} for {} because {}.", notifier.name(), release.version, reason);
+                        gate.wait(release.version);
+                    }
+                }
+                match notifier.notify(release) {
+                    Ok(_) => {
+                        info!("Sent {} for {}.", notifier.name(), release.version);
+                    }
+                    Err(e) => {
+                        error!("Unable to send {} for {}! Error: {}", notifier.name(), release.version, e);
+                    }
+                }
+            })
+        }).collect()
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use crate::build_version::BuildVersion;
+use crate::download::DownloadProgress;
+use crate::health::SourceHealth;
+use crate::release::DetectedRelease;
+
+/// An event type.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Event {
+    VersionDetected,
+    VersionReminder,
+    VersionAcknowledged,
+    AckReset,
+    ScanFailed,
+    SourceUnreachable,
+    HealthChanged,
+    DownloadProgress,
+    ConfigReloaded
+}
+
+impl Event {
+    pub const ALL: [Event; 9] = [
+        Event::VersionDetected,
+        Event::VersionReminder,
+        Event::VersionAcknowledged,
+        Event::AckReset,
+        Event::ScanFailed,
+        Event::SourceUnreachable,
+        Event::HealthChanged,
+        Event::DownloadProgress,
+        Event::ConfigReloaded
+    ];
+}
+
+/// A version was seen on a source for the first time.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VersionDetected {
+    pub source: String,
+    pub version: BuildVersion,
+    pub release: DetectedRelease
+}
+
+impl VersionDetected {
+    pub fn new(source: &str, version: BuildVersion) -> Self{
+        Self { source: source.into(), version, release: DetectedRelease::new(source, version) }
+    }
+}
+
+impl Display for VersionDetected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.version)
+    }
+}
+
+/// The latest version of a source is still unacknowledged.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VersionReminder {
+    pub source: String,
+    pub version: BuildVersion,
+    pub count: u32
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VersionAcknowledged {
+    pub source: String,
+    pub version: BuildVersion,
+    pub note: Option<String>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AckReset {
+    pub source: String,
+    /// The version acknowledged after the reset, or the default version if none.
+    pub acknowledged: BuildVersion
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ScanFailed {
+    pub source: String,
+    pub error: String
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SourceUnreachable {
+    pub source: String,
+    pub location: String,
+    pub error: String
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ConfigReloaded {
+    pub interval_seconds: u32,
+    pub naggy: bool
+}
+
+/// An event together with its payload.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum Message {
+    VersionDetected(VersionDetected),
+    VersionReminder(VersionReminder),
+    VersionAcknowledged(VersionAcknowledged),
+    AckReset(AckReset),
+    ScanFailed(ScanFailed),
+    SourceUnreachable(SourceUnreachable),
+    HealthChanged(SourceHealth),
+    DownloadProgress(DownloadProgress),
+    ConfigReloaded(ConfigReloaded)
+}
+
+impl Message {
+    pub fn event(&self) -> Event {
+        match self {
+            Message::VersionDetected(_) => Event::VersionDetected,
+            Message::VersionReminder(_) => Event::VersionReminder,
+            Message::VersionAcknowledged(_) => Event::VersionAcknowledged,
+            Message::AckReset(_) => Event::AckReset,
+            Message::ScanFailed(_) => Event::ScanFailed,
+            Message::SourceUnreachable(_) => Event::SourceUnreachable,
+            Message::HealthChanged(_) => Event::HealthChanged,
+            Message::DownloadProgress(_) => Event::DownloadProgress,
+            Message::ConfigReloaded(_) => Event::ConfigReloaded
+        }
+    }
+}
+
+/// Identifies a subscription for the lifetime of its publisher.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct SubscriptionId(u64);
+
+/// What to do with a new message when a subscriber's queue is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backpressure {
+    DropNewest,
+    DropOldest,
+    /// Wait up to the given time for room, then drop the new message.
+    Block(Duration)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SubscriberOptions {
+    pub capacity: usize,
+    pub backpressure: Backpressure
+}
+
+impl Default for SubscriberOptions {
+    fn default() -> Self {
+        Self { capacity: 64, backpressure: Backpressure::DropOldest }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubscriberMetrics {
+    pub id: SubscriptionId,
+    pub delivered: u64,
+    pub dropped: u64,
+    pub panicked: u64,
+    pub queued: usize
+}
+
+#[derive(Default)]
+struct Counters {
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    panicked: AtomicU64
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<Message>,
+    /// The worker is handling a message it popped.
+    busy: bool,
+    closed: bool
+}
+
+/// A bounded queue feeding one subscriber's worker thread.
+#[derive(Default)]
+struct Queue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    idle: Condvar
+}
+
+impl Queue {
+    /// Queues a message, returning how many messages were dropped to honour the backpressure policy.
+    fn push(&self, message: Message, options: &SubscriberOptions) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return 0;
+        }
+
+        let mut dropped = 0;
+        if state.messages.len() >= options.capacity {
+            match options.backpressure {
+                Backpressure::DropNewest => {
+                    return 1;
+                }
+                Backpressure::DropOldest => {
+                    state.messages.pop_front();
+                    dropped = 1;
+                }
+                Backpressure::Block(timeout) => {
+                    let (s, _) = self.not_full
+                        .wait_timeout_while(state, timeout, |s| !s.closed && s.messages.len() >= options.capacity)
+                        .unwrap();
+                    state = s;
+                    if state.closed {
+                        return 0;
+                    }
+                    if state.messages.len() >= options.capacity {
+                        return 1;
+                    }
+                }
+            }
+        }
+
+        state.messages.push_back(message);
+        self.not_empty.notify_one();
+        dropped
+    }
+
+    /// Waits for the next message, or `None` once the queue is closed.
+    fn pop(&self) -> Option<Message> {
+        let mut state = self.not_empty
+            .wait_while(self.state.lock().unwrap(), |s| !s.closed && s.messages.is_empty())
+            .unwrap();
+        if state.closed {
+            return None;
+        }
+
+        let message = state.messages.pop_front();
+        state.busy = true;
+        self.not_full.notify_one();
+        message
+    }
+
+    /// Marks the popped message as handled.
+    fn done(&self) {
+        self.state.lock().unwrap().busy = false;
+        self.idle.notify_all();
+    }
+
+    /// Waits until every queued message has been handled.
+    fn wait_idle(&self) {
+        let _state = self.idle
+            .wait_while(self.state.lock().unwrap(), |s| !s.closed && (s.busy || !s.messages.is_empty()))
+            .unwrap();
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.messages.clear();
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        self.idle.notify_all();
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().messages.len()
+    }
+}
+
+struct Subscription {
+    id: SubscriptionId,
+    options: SubscriberOptions,
+    queue: Arc<Queue>,
+    counters: Arc<Counters>
+}
+
+impl Subscription {
+    fn spawn(id: SubscriptionId, options: SubscriberOptions, func: Box<dyn Fn(&Message) + Send + Sync + 'static>) -> Self {
+        let queue = Arc::new(Queue::default());
+        let counters = Arc::new(Counters::default());
+
+        let (q, c) = (queue.clone(), counters.clone());
+        thread::Builder::new()
+            .name(format!("subscriber-{}", id.0))
+            .spawn(move || {
+                while let Some(message) = q.pop() {
+                    match panic::catch_unwind(AssertUnwindSafe(|| func(&message))) {
+                        Ok(_) => {
+                            c.delivered.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(_) => {
+                            c.panicked.fetch_add(1, Ordering::Relaxed);
+                            error!("Subscriber {} panicked while handling {:?}.", id.0, message.event());
+                        }
+                    }
+                    q.done();
+                }
+            })
+            .unwrap();
+
+        Self { id, options, queue, counters }
+    }
+
+    fn deliver(&self, message: Message) {
+        let dropped = self.queue.push(message, &self.options);
+        if dropped > 0 {
+            self.counters.dropped.fetch_add(dropped, Ordering::Relaxed);
+            warn!("Subscriber {} is falling behind, dropped an event.", self.id.0);
+        }
+    }
+
+    fn metrics(&self) -> SubscriberMetrics {
+        SubscriberMetrics {
+            id: self.id,
+            delivered: self.counters.delivered.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            panicked: self.counters.panicked.load(Ordering::Relaxed),
+            queued: self.queue.len()
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    events: HashMap<Event, Vec<Arc<Subscription>>>
+}
+
+impl Subscribers {
+    fn remove(&mut self, id: SubscriptionId) {
+        for listeners in self.events.values_mut() {
+            listeners.retain(|x| {
+                if x.id == id {
+                    x.queue.close();
+                }
+                x.id != id
+            });
+        }
+    }
+}
+
+/// Keeps a subscription alive. Dropping the handle unsubscribes.
+#[must_use = "dropping the handle unsubscribes immediately"]
+pub struct SubscriptionHandle {
+    id: SubscriptionId,
+    subscribers: Weak<Mutex<Subscribers>>
+}
+
+impl SubscriptionHandle {
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+}
+
+impl Drop for SubscriptionHandle {
+    fn drop(&mut self) {
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            subscribers.lock().unwrap().remove(self.id);
+        }
+    }
+}
+
+/// Publisher sends events to subscribers (listeners).
+///
+/// Every subscriber gets its own bounded queue and worker thread, so `notify` never waits on a
+/// listener (unless it asked for [`Backpressure::Block`]) and a panicking listener only loses the
+/// message it panicked on, as long as panics unwind (`panic = "abort"` takes the whole app down).
+/// Once unsubscribing returns, queued messages are discarded and no new call is started, although a
+/// call already in progress runs to completion.
+#[derive(Clone, Default)]
+pub struct Publisher {
+    subscribers: Arc<Mutex<Subscribers>>
+}
+
+impl Publisher {
+    pub fn subscribe<F>(&self, event_type: Event, func: F) -> SubscriptionHandle
+        where F: Fn(&Message) + Send + Sync + 'static {
+        self.subscribe_with(&[event_type], SubscriberOptions::default(), func)
+    }
+
+    /// Subscribes a single listener to several events, sharing one handle.
+    pub fn subscribe_many<F>(&self, event_types: &[Event], func: F) -> SubscriptionHandle
+        where F: Fn(&Message) + Send + Sync + 'static {
+        self.subscribe_with(event_types, SubscriberOptions::default(), func)
+    }
+
+    pub fn subscribe_with<F>(&self, event_types: &[Event], options: SubscriberOptions, func: F) -> SubscriptionHandle
+        where F: Fn(&Message) + Send + Sync + 'static {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.next_id += 1;
+        let id = SubscriptionId(subscribers.next_id);
+
+        let subscription = Arc::new(Subscription::spawn(id, options, Box::new(func)));
+        for event_type in event_types {
+            subscribers.events.entry(*event_type).or_default().push(subscription.clone());
+        }
+
+        SubscriptionHandle { id, subscribers: Arc::downgrade(&self.subscribers) }
+    }
+
+    /// Removes a subscription. Unknown ids are ignored.
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.subscribers.lock().unwrap().remove(id);
+    }
+
+    pub fn notify(&self, message: Message) {
+        let listeners = match self.subscribers.lock().unwrap().events.get(&message.event()) {
+            None => return,
+            Some(listeners) => listeners.clone()
+        };
+
+        for listener in listeners {
+            listener.deliver(message.clone());
+        }
+    }
+
+    /// Waits until every subscriber has handled the messages published so far.
+    pub fn flush(&self) {
+        let queues: Vec<Arc<Queue>> = self.subscribers.lock().unwrap().events.values().flatten().map(|s| s.queue.clone()).collect();
+        for queue in queues {
+            queue.wait_idle();
+        }
+    }
+
+    pub fn metrics(&self) -> Vec<SubscriberMetrics> {
+        let subscribers = self.subscribers.lock().unwrap();
+        let mut metrics: Vec<SubscriberMetrics> = Vec::new();
+        for listener in subscribers.events.values().flatten() {
+            if !metrics.iter().any(|m| m.id == listener.id) {
+                metrics.push(listener.metrics());
+            }
+        }
+        metrics.sort_by_key(|m| m.id.0);
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::publisher::{Backpressure, ConfigReloaded, Event, Message, Publisher, ScanFailed, SubscriberOptions};
+
+    const WAIT: Duration = Duration::from_secs(5);
+    const QUIET: Duration = Duration::from_millis(100);
+
+    fn config_reloaded() -> Message {
+        Message::ConfigReloaded(ConfigReloaded { interval_seconds: 1, naggy: false })
+    }
+
+    fn scan_failed(error: &str) -> Message {
+        Message::ScanFailed(ScanFailed { source: String::from("a"), error: error.into() })
+    }
+
+    fn error_of(message: Message) -> String {
+        match message {
+            Message::ScanFailed(f) => f.error,
+            m => panic!("unexpected {:?}", m)
+        }
+    }
+
+    #[test]
+    fn delivers_only_subscribed_events() {
+        let publisher = Publisher::default();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let _handle = publisher.subscribe(Event::ConfigReloaded, move |m| tx.lock().unwrap().send(m.clone()).unwrap());
+
+        publisher.notify(config_reloaded());
+        publisher.notify(scan_failed(""));
+
+        assert_eq!(rx.recv_timeout(WAIT).unwrap(), config_reloaded());
+        assert!(rx.recv_timeout(QUIET).is_err());
+    }
+
+    #[test]
+    fn flush_waits_for_listeners_to_finish() {
+        let publisher = Publisher::default();
+        let handled = Arc::new(AtomicUsize::new(0));
+        let h = handled.clone();
+        let _handle = publisher.subscribe(Event::ConfigReloaded, move |_| {
+            thread::sleep(Duration::from_millis(20));
+            h.fetch_add(1, Ordering::SeqCst);
+        });
+
+        for _ in 0..3 {
+            publisher.notify(config_reloaded());
+        }
+        publisher.flush();
+        assert_eq!(handled.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn dropping_the_handle_unsubscribes() {
+        let publisher = Publisher::default();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let handle = publisher.subscribe_many(&Event::ALL, move |m| tx.lock().unwrap().send(m.clone()).unwrap());
+        let other = publisher.subscribe(Event::ConfigReloaded, |_| {});
+        assert_ne!(handle.id(), other.id());
+
+        publisher.notify(config_reloaded());
+        rx.recv_timeout(WAIT).unwrap();
+        drop(handle);
+        publisher.notify(config_reloaded());
+
+        assert!(rx.recv_timeout(QUIET).is_err());
+        assert_eq!(publisher.metrics().len(), 1);
+    }
+
+    #[test]
+    fn unsubscribing_unknown_ids_does_not_panic() {
+        let publisher = Publisher::default();
+        let handle = publisher.subscribe(Event::AckReset, |_| {});
+        publisher.unsubscribe(handle.id());
+        drop(handle);
+        drop(publisher);
+    }
+
+    #[test]
+    fn listener_can_unsubscribe_itself() {
+        let publisher = Publisher::default();
+        let slot: Arc<Mutex<Option<_>>> = Arc::new(Mutex::new(None));
+        let count = Arc::new(AtomicUsize::new(0));
+        let (s, c) = (slot.clone(), count.clone());
+        *slot.lock().unwrap() = Some(publisher.subscribe(Event::ConfigReloaded, move |_| {
+            c.fetch_add(1, Ordering::SeqCst);
+            s.lock().unwrap().take();
+        }));
+
+        publisher.notify(config_reloaded());
+        publisher.notify(config_reloaded());
+        thread::sleep(QUIET);
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn subscribe_and_unsubscribe_race_with_notify() {
+        let publisher = Publisher::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let delivered = Arc::new(AtomicUsize::new(0));
+
+        let notifiers: Vec<_> = (0..2).map(|_| {
+            let (publisher, stop) = (publisher.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    publisher.notify(config_reloaded());
+                }
+            })
+        }).collect();
+
+        let subscribers: Vec<_> = (0..4).map(|_| {
+            let (publisher, delivered) = (publisher.clone(), delivered.clone());
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let d = delivered.clone();
+                    let handle = publisher.subscribe(Event::ConfigReloaded, move |_| { d.fetch_add(1, Ordering::SeqCst); });
+                    thread::yield_now();
+                    drop(handle);
+                }
+            })
+        }).collect();
+
+        for s in subscribers {
+            s.join().unwrap();
+        }
+        stop.store(true, Ordering::SeqCst);
+        for n in notifiers {
+            n.join().unwrap();
+        }
+
+        // Every subscription is gone, so once calls in progress finish nothing else may be delivered.
+        thread::sleep(QUIET);
+        let after = delivered.load(Ordering::SeqCst);
+        publisher.notify(config_reloaded());
+        thread::sleep(QUIET);
+        assert_eq!(delivered.load(Ordering::SeqCst), after);
+        assert!(publisher.metrics().is_empty());
+    }
+
+    #[test]
+    fn slow_listener_does_not_block_notify() {
+        let publisher = Publisher::default();
+        let _handle = publisher.subscribe(Event::ConfigReloaded, |_| thread::sleep(Duration::from_millis(200)));
+
+        let started = Instant::now();
+        for _ in 0..10 {
+            publisher.notify(config_reloaded());
+        }
+
+        assert!(started.elapsed() < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn panicking_listener_is_isolated() {
+        let publisher = Publisher::default();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let _panicky = publisher.subscribe(Event::ScanFailed, move |m| {
+            let error = error_of(m.clone());
+            if error == "boom" {
+                panic!("boom");
+            }
+            tx.lock().unwrap().send(error).unwrap();
+        });
+
+        publisher.notify(scan_failed("boom"));
+        publisher.notify(scan_failed("after"));
+
+        assert_eq!(rx.recv_timeout(WAIT).unwrap(), "after");
+        let started = Instant::now();
+        while publisher.metrics()[0].delivered == 0 && started.elapsed() < WAIT {
+            thread::yield_now();
+        }
+        let metrics = &publisher.metrics()[0];
+        assert_eq!((metrics.delivered, metrics.panicked), (1, 1));
+    }
+
+    fn blocked_subscriber(backpressure: Backpressure) -> (Vec<String>, u64) {
+        let publisher = Publisher::default();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (seen_tx, seen_rx) = mpsc::channel();
+        let (started_tx, release_rx, seen_tx) = (Mutex::new(started_tx), Mutex::new(release_rx), Mutex::new(seen_tx));
+        let options = SubscriberOptions { capacity: 2, backpressure };
+        let _handle = publisher.subscribe_with(&[Event::ScanFailed], options, move |m| {
+            let error = error_of(m.clone());
+            if error == "0" {
+                started_tx.lock().unwrap().send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+            }
+            seen_tx.lock().unwrap().send(error).unwrap();
+        });
+
+        publisher.notify(scan_failed("0"));
+        started_rx.recv_timeout(WAIT).unwrap();
+        for i in 1..5 {
+            publisher.notify(scan_failed(&i.to_string()));
+        }
+        release_tx.send(()).unwrap();
+
+        let seen = (0..3).map(|_| seen_rx.recv_timeout(WAIT).unwrap()).collect();
+        assert!(seen_rx.recv_timeout(QUIET).is_err());
+        (seen, publisher.metrics()[0].dropped)
+    }
+
+    #[test]
+    fn drop_oldest_keeps_latest_messages() {
+        assert_eq!(blocked_subscriber(Backpressure::DropOldest), (vec![String::from("0"), String::from("3"), String::from("4")], 2));
+    }
+
+    #[test]
+    fn drop_newest_keeps_earliest_messages() {
+        assert_eq!(blocked_subscriber(Backpressure::DropNewest), (vec![String::from("0"), String::from("1"), String::from("2")], 2));
+    }
+
+    #[test]
+    fn block_gives_up_after_timeout() {
+        let dropped = blocked_subscriber(Backpressure::Block(Duration::from_millis(10))).1;
+        assert_eq!(dropped, 2);
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::build_version::BuildVersion;
+
+/// A version together with what it was found in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectedRelease {
+    pub source: String,
+    pub version: BuildVersion,
+    /// The file or URL the version was read from, if the checker knows.
+    pub path: Option<String>,
+    pub size: Option<u64>,
+    pub modified: Option<DateTime<Utc>>,
+    /// The file name as found, before parsing.
+    pub raw_name: Option<String>
+}
+
+impl DetectedRelease {
+    pub fn new(source: &str, version: BuildVersion) -> Self {
+        Self { source: source.into(), version, ..Default::default() }
+    }
+
+    /// The values available to templates: `{source}`, `{version}`, `{path}`, `{name}` and `{size}`.
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("source", self.source.clone()),
+            ("version", self.version.to_string()),
+            ("path", self.path.clone().unwrap_or_default()),
+            ("name", self.raw_name.clone().unwrap_or_default()),
+            ("size", self.size.map(|s| s.to_string()).unwrap_or_default())
+        ]
+    }
+
+    fn local_path(&self) -> Result<&Path, anyhow::Error> {
+        match &self.path {
+            Some(p) if Path::new(p).exists() => Ok(Path::new(p)),
+            Some(p) => Err(anyhow!("{} no longer exists.", p)),
+            None => Err(anyhow!("No file is known for {}.", self.version))
+        }
+    }
+
+    /// Opens the release's file with its default application.
+    pub fn open(&self) -> Result<(), anyhow::Error> {
+        open::that(self.local_path()?)?;
+        Ok(())
+    }
+
+    /// Shows the folder holding the release's file.
+    pub fn reveal(&self) -> Result<(), anyhow::Error> {
+        let path = self.local_path()?;
+        open::that(path.parent().unwrap_or(path))?;
+        Ok(())
+    }
+}
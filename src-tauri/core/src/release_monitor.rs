@@ -0,0 +1,473 @@
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local, Utc};
+use tracing::{error, info};
+use crate::ack_history::AckRecord;
+use crate::build_version::BuildVersion;
+use crate::clock::{Clock, SystemClock};
+use crate::health::{Backoff, SourceHealth};
+use crate::metrics::ScanMetrics;
+use crate::publisher::{AckReset, Event, Message, Publisher, ScanFailed, SourceUnreachable, SubscriberMetrics, SubscriptionHandle, VersionAcknowledged, VersionDetected, VersionReminder};
+use crate::release::DetectedRelease;
+use crate::source_state::{SourceState, SourceStateMachine, Transition};
+use crate::state_store::DetectionStore;
+use crate::statistics;
+use crate::statistics::{ReleaseEntry, ReleaseStatistics};
+use crate::version_checker::{UnreachableSourceError, VersionChecker};
+use crate::version_updater::VersionUpdater;
+
+/// Everything a scan needs, shared between the monitor and its background thread.
+struct Scanner {
+    source: String,
+    publisher: Publisher,
+    version_checker: Arc<dyn VersionChecker + Send + Sync>,
+    version_updater: Arc<dyn VersionUpdater + Send + Sync>,
+    detection_store: Arc<dyn DetectionStore + Send + Sync>,
+    state: Mutex<SourceStateMachine>,
+    latest_release: Mutex<Option<DetectedRelease>>,
+    health: Mutex<SourceHealth>,
+    metrics: Mutex<ScanMetrics>
+}
+
+impl Scanner {
+    fn scan(&self, now: DateTime<Utc>) -> Result<Option<Transition>, anyhow::Error> {
+        let started = Instant::now();
+        let result = self.check(now);
+        self.metrics.lock().unwrap().record(started.elapsed(), result.as_ref().err());
+        result
+    }
+
+    fn check(&self, now: DateTime<Utc>) -> Result<Option<Transition>, anyhow::Error> {
+        let latest_release = match self.version_checker.get_latest_release(&self.source) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to check {}. Error: {}", self.source, e);
+                let unreachable = e.downcast_ref::<UnreachableSourceError>().is_some();
+                let changed = self.health.lock().unwrap().record_failure(&e.to_string(), unreachable, now);
+                if changed {
+                    self.publish_health();
+                }
+
+                let message = match e.downcast_ref::<UnreachableSourceError>() {
+                    Some(u) => Message::SourceUnreachable(SourceUnreachable {
+                        source: self.source.clone(),
+                        location: u.location.clone(),
+                        error: u.error.to_string()
+                    }),
+                    None => Message::ScanFailed(ScanFailed { source: self.source.clone(), error: e.to_string() })
+                };
+                self.publisher.notify(message);
+                return Err(e);
+            }
+        };
+
+        if self.health.lock().unwrap().record_success(now) {
+            info!("{} is reachable again.", self.source);
+            self.publish_health();
+        }
+
+        let latest_version = latest_release.version;
+        if latest_version == BuildVersion::default() {
+            return Ok(None);
+        }
+        *self.latest_release.lock().unwrap() = Some(latest_release.clone());
+
+        if self.detection_store.last_seen() != latest_version {
+            self.detection_store.record_seen(latest_version);
+        }
+
+        let acked_version = self.version_updater.get_version();
+        let transition = self.state.lock().unwrap().on_scan(latest_version, acked_version, now);
+        match transition {
+            None => {}
+            Some(Transition::Detected(version)) => {
+                info!("Detected new version {} on {}.", version, self.source);
+                self.detection_store.set_last_notified(version, now);
+                let detected = VersionDetected { source: self.source.clone(), version, release: latest_release };
+                self.publisher.notify(Message::VersionDetected(detected));
+            }
+            Some(Transition::Reminder { version, count }) => {
+                info!("Version {} on {} is still unacknowledged. Reminder {}.", version, self.source, count);
+                self.detection_store.set_last_notified(version, now);
+                self.publisher.notify(Message::VersionReminder(VersionReminder { source: self.source.clone(), version, count }));
+            }
+            Some(Transition::Acknowledged(version)) => {
+                info!("Version {} on {} is acknowledged.", version, self.source);
+            }
+        }
+
+        Ok(transition)
+    }
+
+    fn publish_health(&self) {
+        let health = self.health.lock().unwrap().clone();
+        self.publisher.notify(Message::HealthChanged(health));
+    }
+}
+
+pub struct ReleaseMonitor {
+    scanner: Arc<Scanner>,
+    clock: Arc<dyn Clock + Send + Sync>,
+    stop: Arc<AtomicBool>,
+    interval_seconds: Arc<AtomicU32>,
+    following: bool
+}
+
+impl ReleaseMonitor {
+    pub fn new(source: &str,
+               version_checker: Arc<dyn VersionChecker + Send + Sync>,
+               version_updater: Arc<dyn VersionUpdater + Send + Sync>,
+               detection_store: Arc<dyn DetectionStore + Send + Sync>,
+                interval_seconds: u32) -> ReleaseMonitor {
+        let state = SourceStateMachine::resume(chrono::Duration::seconds(interval_seconds as i64),
+                                               detection_store.last_seen(),
+                                               version_updater.get_version(),
+                                               detection_store.last_notified());
+        Self {
+            scanner: Arc::new(Scanner {
+                source: source.into(),
+                publisher: Publisher::default(),
+                version_checker,
+                version_updater,
+                detection_store,
+                state: Mutex::new(state),
+                latest_release: Mutex::new(None),
+                health: Mutex::new(SourceHealth::new(source)),
+                metrics: Mutex::new(ScanMetrics::default())
+            }),
+            clock: Arc::new(SystemClock),
+            stop: Arc::new(AtomicBool::new(false)),
+            interval_seconds: Arc::new(AtomicU32::new(interval_seconds)),
+            following: false
+        }
+    }
+
+    /// Replaces the system clock, e.g. with a virtual one in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock + Send + Sync>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// For a monitor which passes on what another one publishes, e.g. a daemon's, instead of
+    /// scanning. Acknowledgements reach the other monitor through the updater and come back with
+    /// its events, so they aren't published here too.
+    pub fn following(mut self) -> Self {
+        self.following = true;
+        self
+    }
+
+    pub fn acknowledge(&self, version: BuildVersion, note: Option<String>){
+        self.scanner.version_updater.set_version(version, note.clone());
+        if !self.following {
+            self.publish(Message::VersionAcknowledged(VersionAcknowledged { source: self.scanner.source.clone(), version, note }));
+        }
+    }
+
+    pub fn undo_acknowledge(&self) -> BuildVersion {
+        let acknowledged = self.scanner.version_updater.undo();
+        if !self.following {
+            self.publish(Message::AckReset(AckReset { source: self.scanner.source.clone(), acknowledged }));
+        }
+        acknowledged
+    }
+
+    /// Changes how often the source is scanned and reminded about, taking effect after the current wait.
+    pub fn set_interval(&self, interval_seconds: u32) {
+        self.interval_seconds.store(interval_seconds, Ordering::Relaxed);
+        self.scanner.state.lock().unwrap().set_reminder_interval(chrono::Duration::seconds(interval_seconds as i64));
+    }
+
+    pub fn publish(&self, message: Message) {
+        self.scanner.publisher.notify(message);
+    }
+
+    pub fn ack_history(&self) -> Vec<AckRecord> {
+        self.scanner.version_updater.history()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.scanner.source
+    }
+
+    /// The latest version seen by the last scans, without scanning again.
+    pub fn last_seen(&self) -> BuildVersion {
+        self.scanner.detection_store.last_seen()
+    }
+
+    /// The latest release found since the monitor started, with its file details. A following
+    /// monitor asks its checker.
+    pub fn latest_release(&self) -> Option<DetectedRelease> {
+        if self.following {
+            return self.scanner.version_checker.get_latest_release(&self.scanner.source).ok();
+        }
+        self.scanner.latest_release.lock().unwrap().clone()
+    }
+
+    pub fn state(&self) -> SourceState {
+        self.scanner.state.lock().unwrap().state()
+    }
+
+    pub fn health(&self) -> SourceHealth {
+        self.scanner.health.lock().unwrap().clone()
+    }
+
+    pub fn scan_metrics(&self) -> ScanMetrics {
+        self.scanner.metrics.lock().unwrap().clone()
+    }
+
+    pub fn release_history(&self) -> Vec<ReleaseEntry> {
+        statistics::release_history(&self.scanner.detection_store.detections(), &self.scanner.version_updater.history())
+    }
+
+    pub fn release_statistics(&self) -> ReleaseStatistics {
+        statistics::release_statistics(&self.scanner.source, &self.release_history(), self.clock.now(), &Local)
+    }
+
+    pub fn stop(&mut self){
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn subscribe<F>(&self, event_type: Event, func: F) -> SubscriptionHandle
+        where F: Fn(&Message) + Send + Sync + 'static {
+        self.scanner.publisher.subscribe(event_type, func)
+    }
+
+    pub fn event_metrics(&self) -> Vec<SubscriberMetrics> {
+        self.scanner.publisher.metrics()
+    }
+
+    pub fn subscribe_many<F>(&self, event_types: &[Event], func: F) -> SubscriptionHandle
+        where F: Fn(&Message) + Send + Sync + 'static {
+        self.scanner.publisher.subscribe_many(event_types, func)
+    }
+
+    /// Checks the source once, publishing whatever changed.
+    pub fn scan_once(&self, now: DateTime<Utc>) -> Result<Option<Transition>, anyhow::Error> {
+        self.scanner.scan(now)
+    }
+
+    pub fn start(&self) -> Result<(), anyhow::Error> {
+        let scanner = self.scanner.clone();
+        let clock = self.clock.clone();
+        let stop = self.stop.clone();
+        let interval = self.interval_seconds.clone();
+        let backoff = Backoff::default();
+        thread::spawn(move ||{
+            let mut wait = Duration::from_secs(interval.load(Ordering::Relaxed) as u64);
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                clock.sleep(wait);
+
+                let _ = scanner.scan(clock.now());
+
+                // Retry a failing source sooner at first, then back off.
+                let failures = scanner.health.lock().unwrap().consecutive_failures;
+                wait = match failures {
+                    0 => Duration::from_secs(interval.load(Ordering::Relaxed) as u64),
+                    n => backoff.delay(n)
+                };
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration as StdDuration;
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use crate::build_version::BuildVersion;
+    use crate::clock::Clock;
+    use crate::health::HealthStatus;
+    use crate::publisher::{Event, Message, SubscriptionHandle};
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::source_state::{SourceState, Transition};
+    use crate::state_store::{DetectionStore, SqliteStateStore};
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater, ManualClock};
+
+    const INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+    fn version(t: i32) -> BuildVersion {
+        BuildVersion { major: 1, minor: 2, patch: 3, t }
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap()
+    }
+
+    struct Harness {
+        checker: Arc<FakeVersionChecker>,
+        clock: Arc<ManualClock>,
+        monitor: ReleaseMonitor,
+        events: mpsc::Receiver<Message>,
+        _subscription: SubscriptionHandle
+    }
+
+    impl Harness {
+        fn new(store: Arc<SqliteStateStore>) -> Self {
+            let checker = Arc::new(FakeVersionChecker::default());
+            let clock = Arc::new(ManualClock::new(start()));
+            let monitor = ReleaseMonitor::new("a", checker.clone(), Arc::new(FakeVersionUpdater::new("a")), store, INTERVAL.as_secs() as u32)
+                .with_clock(clock.clone());
+            let (tx, rx) = mpsc::channel();
+            let tx = Mutex::new(tx);
+            let events: Vec<Event> = Event::ALL.into_iter().filter(|e| *e != Event::HealthChanged).collect();
+            let subscription = monitor.subscribe_many(&events, move |m| { let _ = tx.lock().unwrap().send(m.clone()); });
+            Self { checker, clock, monitor, events: rx, _subscription: subscription }
+        }
+
+        /// What was published since the last call, once every listener has caught up.
+        fn published(&self) -> Option<Message> {
+            self.monitor.scanner.publisher.flush();
+            self.events.try_recv().ok()
+        }
+
+        /// Scans one interval later and returns what was published, if anything.
+        fn tick(&self) -> Option<Message> {
+            self.clock.advance(INTERVAL);
+            let _ = self.monitor.scan_once(self.clock.now());
+            self.published()
+        }
+
+        /// Moves the clock on under the background loop and returns what it published, if anything.
+        /// Waiting for the loop to go back to sleep means any scan that was due has finished.
+        fn step(&self, duration: StdDuration) -> Option<Message> {
+            self.clock.wait_for_sleeper();
+            self.clock.advance(duration);
+            self.clock.wait_for_sleeper();
+            self.published()
+        }
+    }
+
+    #[test]
+    fn walks_through_detected_reminding_and_acknowledged() {
+        let h = Harness::new(Arc::new(SqliteStateStore::open_in_memory("a").unwrap()));
+
+        assert_eq!(h.monitor.scan_once(start()).unwrap(), None);
+        assert_eq!(h.monitor.state(), SourceState::Idle);
+
+        h.checker.set_version(version(1));
+        assert_eq!(h.monitor.scan_once(start()).unwrap(), Some(Transition::Detected(version(1))));
+
+        // Not due yet.
+        assert_eq!(h.monitor.scan_once(start() + Duration::seconds(30)).unwrap(), None);
+
+        let reminder = Some(Transition::Reminder { version: version(1), count: 1 });
+        assert_eq!(h.monitor.scan_once(start() + Duration::seconds(60)).unwrap(), reminder);
+        assert_eq!(h.monitor.state(), SourceState::Reminding(version(1)));
+
+        h.monitor.acknowledge(version(1), None);
+        assert_eq!(h.monitor.scan_once(start() + Duration::seconds(120)).unwrap(), Some(Transition::Acknowledged(version(1))));
+        assert_eq!(h.monitor.scan_once(start() + Duration::seconds(180)).unwrap(), None);
+
+        h.checker.set_version(version(2));
+        assert_eq!(h.monitor.scan_once(start() + Duration::seconds(240)).unwrap(), Some(Transition::Detected(version(2))));
+    }
+
+    #[test]
+    fn background_loop_publishes_detection_and_reminders() {
+        let h = Harness::new(Arc::new(SqliteStateStore::open_in_memory("a").unwrap()));
+        h.monitor.start().unwrap();
+
+        assert!(h.step(INTERVAL).is_none());
+
+        h.checker.set_version(version(1));
+        match h.step(INTERVAL) {
+            Some(Message::VersionDetected(d)) => assert_eq!(d.version, version(1)),
+            m => panic!("Expected a detection, got {:?}", m)
+        }
+        match h.step(INTERVAL) {
+            Some(Message::VersionReminder(r)) => assert_eq!((r.version, r.count), (version(1), 1)),
+            m => panic!("Expected a reminder, got {:?}", m)
+        }
+        match h.step(INTERVAL) {
+            Some(Message::VersionReminder(r)) => assert_eq!(r.count, 2),
+            m => panic!("Expected a reminder, got {:?}", m)
+        }
+
+        h.monitor.acknowledge(version(1), None);
+        assert!(matches!(h.published(), Some(Message::VersionAcknowledged(_))));
+        assert!(h.step(INTERVAL).is_none());
+    }
+
+    #[test]
+    fn undoing_an_acknowledgement_reminds_again() {
+        let h = Harness::new(Arc::new(SqliteStateStore::open_in_memory("a").unwrap()));
+        h.checker.set_version(version(1));
+        h.monitor.scan_once(start()).unwrap();
+        h.monitor.acknowledge(version(1), None);
+        h.monitor.scan_once(start()).unwrap();
+
+        assert_eq!(h.monitor.undo_acknowledge(), BuildVersion::default());
+
+        let reminder = Some(Transition::Reminder { version: version(1), count: 1 });
+        assert_eq!(h.monitor.scan_once(start() + Duration::seconds(1)).unwrap(), reminder);
+    }
+
+    #[test]
+    fn checker_errors_are_published_and_scanning_continues() {
+        let h = Harness::new(Arc::new(SqliteStateStore::open_in_memory("a").unwrap()));
+
+        h.checker.unreachable("//share/builds");
+        match h.tick() {
+            Some(Message::SourceUnreachable(u)) => assert_eq!(u.location, "//share/builds"),
+            m => panic!("Expected the source to be unreachable, got {:?}", m)
+        }
+
+        h.checker.fail("bad listing");
+        match h.tick() {
+            Some(Message::ScanFailed(f)) => assert_eq!(f.error, "bad listing"),
+            m => panic!("Expected a failed scan, got {:?}", m)
+        }
+
+        h.checker.set_version(version(1));
+        assert!(matches!(h.tick(), Some(Message::VersionDetected(_))));
+    }
+
+    #[test]
+    fn failing_source_backs_off_and_recovers() {
+        let h = Harness::new(Arc::new(SqliteStateStore::open_in_memory("a").unwrap()));
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let _health = h.monitor.subscribe(Event::HealthChanged, move |m| { let _ = tx.lock().unwrap().send(m.clone()); });
+        h.monitor.start().unwrap();
+
+        h.checker.unreachable("//share/builds");
+        assert!(matches!(h.step(INTERVAL), Some(Message::SourceUnreachable(_))));
+        match rx.try_recv() {
+            Ok(Message::HealthChanged(health)) => assert_eq!(health.status, HealthStatus::Unreachable),
+            m => panic!("Expected a health change, got {:?}", m)
+        }
+
+        // Retried after 5s, then after 10s.
+        assert!(matches!(h.step(StdDuration::from_secs(5)), Some(Message::SourceUnreachable(_))));
+        assert!(h.step(StdDuration::from_secs(5)).is_none());
+        assert!(matches!(h.step(StdDuration::from_secs(5)), Some(Message::SourceUnreachable(_))));
+        assert_eq!(h.monitor.health().consecutive_failures, 3);
+
+        h.checker.set_version(version(1));
+        assert!(matches!(h.step(INTERVAL), Some(Message::VersionDetected(_))));
+        let health = h.monitor.health();
+        assert_eq!(health.status, HealthStatus::Ok);
+        assert_eq!(health.last_success, Some(start() + chrono::Duration::seconds(60 + 5 + 10 + 60)));
+        assert!(matches!(rx.try_recv(), Ok(Message::HealthChanged(_))));
+    }
+
+    #[test]
+    fn restart_does_not_detect_the_same_version_again() {
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        store.record_seen(version(1));
+
+        let h = Harness::new(store);
+        h.checker.set_version(version(1));
+
+        assert_eq!(h.monitor.state(), SourceState::Reminding(version(1)));
+        assert!(matches!(h.monitor.scan_once(start()).unwrap(), Some(Transition::Reminder { .. })));
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use crate::ack_history::AckRecord;
+use crate::build_version::BuildVersion;
+use crate::health::SourceHealth;
+use crate::publisher::SubscriberMetrics;
+use crate::release::DetectedRelease;
+use crate::release_monitor::ReleaseMonitor;
+use crate::source_state::SourceState;
+use crate::statistics::{ReleaseEntry, ReleaseStatistics};
+use crate::version_checker::VersionChecker;
+use crate::version_updater::VersionUpdater;
+
+/// A source's latest version, acknowledgement and health, as last scanned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub source: String,
+    pub latest: BuildVersion,
+    pub acknowledged: BuildVersion,
+    pub state: SourceState,
+    pub health: SourceHealth,
+    /// The latest release with its file details, if one was found since the monitor started.
+    #[serde(default)]
+    pub release: Option<DetectedRelease>
+}
+
+/// The services behind the app's commands, built once at startup and managed as Tauri state.
+#[derive(Clone)]
+pub struct AppServices {
+    pub release_monitor: Arc<ReleaseMonitor>,
+    pub version_checker: Arc<dyn VersionChecker + Send + Sync>,
+    pub version_updater: Arc<dyn VersionUpdater + Send + Sync>
+}
+
+impl AppServices {
+    /// The latest version as text, or an empty string if it can't be retrieved.
+    pub fn latest_version(&self) -> String {
+        match self.version_checker.get_latest_version() {
+            Ok(build_version) => build_version.to_string(),
+            Err(e) => {
+                error!("Unable to retrieve latest version! Error: {}", e);
+                String::new()
+            }
+        }
+    }
+
+    /// The latest release with its file details, if one was found since the app started. Doesn't
+    /// scan.
+    pub fn latest_release(&self) -> Option<DetectedRelease> {
+        self.release_monitor.latest_release()
+    }
+
+    /// Doesn't scan; `latest` is what the monitor saw last.
+    pub fn status(&self) -> StatusReport {
+        StatusReport {
+            source: self.release_monitor.source().into(),
+            latest: self.release_monitor.last_seen(),
+            acknowledged: self.version_updater.get_version(),
+            state: self.release_monitor.state(),
+            health: self.release_monitor.health(),
+            release: self.release_monitor.latest_release()
+        }
+    }
+
+    /// Whether `version` is acknowledged. Nothing to acknowledge counts as acknowledged.
+    pub fn is_acked(&self, version: &str) -> bool {
+        match BuildVersion::parse(version) {
+            Ok(v) if v == BuildVersion::default() => true,
+            Ok(v) => self.version_updater.get_version() == v,
+            Err(e) => {
+                error!("Unable to parse version {}! Error: {}", version, e);
+                false
+            }
+        }
+    }
+
+    pub fn acknowledge(&self, version: &str, note: Option<String>) -> bool {
+        match BuildVersion::parse(version) {
+            Ok(v) if v == BuildVersion::default() => false,
+            Ok(v) => {
+                self.release_monitor.acknowledge(v, note);
+                true
+            }
+            Err(e) => {
+                error!("Unable to parse version {}! Error: {}", version, e);
+                false
+            }
+        }
+    }
+
+    pub fn undo_acknowledge(&self) -> String {
+        self.release_monitor.undo_acknowledge().to_string()
+    }
+
+    /// Most recent first.
+    pub fn ack_history(&self) -> Vec<AckRecord> {
+        let mut history = self.release_monitor.ack_history();
+        history.reverse();
+        history
+    }
+
+    pub fn release_history(&self) -> Vec<ReleaseEntry> {
+        self.release_monitor.release_history()
+    }
+
+    pub fn release_statistics(&self) -> ReleaseStatistics {
+        self.release_monitor.release_statistics()
+    }
+
+    pub fn source_health(&self) -> SourceHealth {
+        self.release_monitor.health()
+    }
+
+    pub fn event_metrics(&self) -> Vec<SubscriberMetrics> {
+        self.release_monitor.event_metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::build_version::BuildVersion;
+    use crate::release_monitor::ReleaseMonitor;
+    use crate::services::AppServices;
+    use crate::state_store::SqliteStateStore;
+    use crate::testing::{FakeVersionChecker, FakeVersionUpdater};
+
+    fn services() -> (Arc<FakeVersionChecker>, AppServices) {
+        let checker = Arc::new(FakeVersionChecker::default());
+        let updater = Arc::new(FakeVersionUpdater::new("a"));
+        let store = Arc::new(SqliteStateStore::open_in_memory("a").unwrap());
+        let release_monitor = Arc::new(ReleaseMonitor::new("a", checker.clone(), updater.clone(), store, 60));
+        (checker.clone(), AppServices { release_monitor, version_checker: checker, version_updater: updater })
+    }
+
+    #[test]
+    fn reports_the_latest_version_or_nothing() {
+        let (checker, services) = services();
+        checker.set_version(BuildVersion::parse("R1.02.03T04").unwrap());
+        assert_eq!(services.latest_version(), "R1.02.03T04");
+
+        checker.fail("bad listing");
+        assert_eq!(services.latest_version(), "");
+    }
+
+    #[test]
+    fn acknowledges_through_the_monitor() {
+        let (_, services) = services();
+        assert!(services.is_acked("R0.00.00T00"));
+        assert!(!services.is_acked("R1.02.03T04"));
+        assert!(!services.acknowledge("R0.00.00T00", None));
+        assert!(!services.acknowledge("not a version", None));
+
+        assert!(services.acknowledge("R1.02.03T04", Some(String::from("installed"))));
+        assert!(services.is_acked("R1.02.03T04"));
+        assert_eq!(services.ack_history()[0].note.as_deref(), Some("installed"));
+
+        assert_eq!(services.undo_acknowledge(), "R0.00.00T00");
+        assert!(!services.is_acked("R1.02.03T04"));
+        assert_eq!(services.ack_history().len(), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::build_version::BuildVersion;
+
+/// Where a source is in the notification lifecycle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "version", rename_all = "snake_case")]
+pub enum SourceState {
+    Idle,
+    Detected(BuildVersion),
+    Reminding(BuildVersion),
+    Acknowledged(BuildVersion)
+}
+
+/// What changed after a scan.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Transition {
+    /// A version was seen for the first time.
+    Detected(BuildVersion),
+    /// A version is still unacknowledged; `count` reminders have been sent for it including this one.
+    Reminder { version: BuildVersion, count: u32 },
+    Acknowledged(BuildVersion)
+}
+
+/// Idle → Detected → Reminding → Acknowledged, for a single source.
+pub struct SourceStateMachine {
+    state: SourceState,
+    reminder_interval: Duration,
+    last_notified: Option<DateTime<Utc>>,
+    reminders: u32
+}
+
+impl SourceStateMachine {
+    pub fn new(reminder_interval: Duration) -> Self {
+        Self { state: SourceState::Idle, reminder_interval, last_notified: None, reminders: 0 }
+    }
+
+    /// Picks up where a previous run left off, so a version seen before a restart isn't detected again
+    /// and isn't reminded about before the interval since the last notification is up.
+    pub fn resume(reminder_interval: Duration,
+                  last_seen: BuildVersion,
+                  acknowledged: BuildVersion,
+                  last_notified: Option<(BuildVersion, DateTime<Utc>)>) -> Self {
+        let mut machine = Self::new(reminder_interval);
+        if last_seen == BuildVersion::default() {
+            machine.state = SourceState::Idle;
+        } else if last_seen == acknowledged {
+            machine.state = SourceState::Acknowledged(last_seen);
+        } else {
+            machine.state = SourceState::Reminding(last_seen);
+            machine.last_notified = last_notified.filter(|(v, _)| *v == last_seen).map(|(_, at)| at);
+        }
+        machine
+    }
+
+    pub fn state(&self) -> SourceState {
+        self.state
+    }
+
+    pub fn set_reminder_interval(&mut self, reminder_interval: Duration) {
+        self.reminder_interval = reminder_interval;
+    }
+
+    pub fn on_scan(&mut self, latest: BuildVersion, acknowledged: BuildVersion, now: DateTime<Utc>) -> Option<Transition> {
+        if latest == BuildVersion::default() {
+            return None;
+        }
+
+        if latest == acknowledged {
+            if self.state == SourceState::Acknowledged(latest) {
+                return None;
+            }
+            self.state = SourceState::Acknowledged(latest);
+            return Some(Transition::Acknowledged(latest));
+        }
+
+        let current = match self.state {
+            SourceState::Idle => None,
+            SourceState::Detected(v) | SourceState::Reminding(v) | SourceState::Acknowledged(v) => Some(v)
+        };
+        if current != Some(latest) {
+            self.state = SourceState::Detected(latest);
+            self.last_notified = Some(now);
+            self.reminders = 0;
+            return Some(Transition::Detected(latest));
+        }
+
+        let due = match (self.state, self.last_notified) {
+            // The acknowledgement was undone, remind straight away.
+            (SourceState::Acknowledged(_), _) | (_, None) => true,
+            (_, Some(last)) => now - last >= self.reminder_interval
+        };
+        if !due {
+            return None;
+        }
+
+        self.state = SourceState::Reminding(latest);
+        self.last_notified = Some(now);
+        self.reminders += 1;
+        Some(Transition::Reminder { version: latest, count: self.reminders })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use crate::build_version::BuildVersion;
+    use crate::source_state::{SourceState, SourceStateMachine, Transition};
+
+    fn version(t: i32) -> BuildVersion {
+        BuildVersion { major: 1, minor: 2, patch: 3, t }
+    }
+
+    #[test]
+    fn resumes_without_detecting_again() {
+        let none = BuildVersion::default();
+        assert_eq!(SourceStateMachine::resume(Duration::minutes(1), none, none, None).state(), SourceState::Idle);
+        assert_eq!(SourceStateMachine::resume(Duration::minutes(1), version(1), version(1), None).state(), SourceState::Acknowledged(version(1)));
+
+        let mut machine = SourceStateMachine::resume(Duration::minutes(1), version(1), none, None);
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap();
+        assert_eq!(machine.on_scan(version(1), none, now), Some(Transition::Reminder { version: version(1), count: 1 }));
+    }
+
+    #[test]
+    fn resumes_reminding_after_the_interval_since_the_last_notification() {
+        let none = BuildVersion::default();
+        let notified = Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap();
+        let mut machine = SourceStateMachine::resume(Duration::minutes(10), version(1), none, Some((version(1), notified)));
+
+        assert_eq!(machine.on_scan(version(1), none, notified + Duration::minutes(5)), None);
+        assert_eq!(machine.on_scan(version(1), none, notified + Duration::minutes(10)), Some(Transition::Reminder { version: version(1), count: 1 }));
+    }
+
+    #[test]
+    fn ignores_missing_versions() {
+        let mut machine = SourceStateMachine::new(Duration::minutes(1));
+        assert_eq!(machine.on_scan(BuildVersion::default(), BuildVersion::default(), Utc::now()), None);
+        assert_eq!(machine.state(), SourceState::Idle);
+    }
+}
@@ -0,0 +1,431 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::Serialize;
+use tracing::{error, info};
+use crate::ack_history;
+use crate::ack_history::{AckAction, AckRecord};
+use crate::build_version::BuildVersion;
+use crate::version_updater::VersionUpdater;
+
+/// The first time a version was seen on a source.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Detection {
+    pub source: String,
+    pub version: BuildVersion,
+    pub first_seen: DateTime<Utc>
+}
+
+/// Remembers what the monitor has seen and notified about, so restarts don't lose it.
+pub trait DetectionStore {
+    fn last_seen(&self) -> BuildVersion;
+    /// Marks `version` as the latest seen version, recording its first-seen time if it is new.
+    fn record_seen(&self, version: BuildVersion);
+    fn detections(&self) -> Vec<Detection>;
+    /// The version last notified or reminded about, and when.
+    fn last_notified(&self) -> Option<(BuildVersion, DateTime<Utc>)>;
+    fn set_last_notified(&self, version: BuildVersion, at: DateTime<Utc>);
+}
+
+/// How long to wait for another process (the GUI, the CLI or the daemon) to finish writing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS acks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL,
+    action TEXT NOT NULL,
+    version TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    user TEXT,
+    note TEXT
+);
+CREATE TABLE IF NOT EXISTS acknowledged (
+    source TEXT PRIMARY KEY,
+    version TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS detections (
+    source TEXT NOT NULL,
+    version TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    PRIMARY KEY (source, version)
+);
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS monitor_state (
+    source TEXT PRIMARY KEY,
+    last_seen TEXT,
+    last_notified TEXT,
+    last_notified_at TEXT
+);
+";
+
+/// SQLite-backed state for one source: acknowledgements, their history, detections and monitor state.
+pub struct SqliteStateStore {
+    conn: Arc<Mutex<Connection>>,
+    source: String
+}
+
+impl SqliteStateStore {
+    pub fn open(path: &Path, source: &str) -> Result<Self, anyhow::Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Self::from_connection(conn, source)
+    }
+
+    pub fn open_in_memory(source: &str) -> Result<Self, anyhow::Error> {
+        Self::from_connection(Connection::open_in_memory()?, source)
+    }
+
+    fn from_connection(conn: Connection, source: &str) -> Result<Self, anyhow::Error> {
+        conn.execute_batch(SCHEMA)?;
+        // Databases from before reminders survived restarts.
+        let has_notified_at: bool = conn.query_row("SELECT COUNT(*) FROM pragma_table_info('monitor_state') WHERE name = 'last_notified_at'",
+                                                   [], |r| r.get(0))?;
+        if !has_notified_at {
+            conn.execute_batch("ALTER TABLE monitor_state ADD COLUMN last_notified_at TEXT")?;
+        }
+        Ok(Self { conn: Arc::new(Mutex::new(conn)), source: source.into() })
+    }
+
+    /// A store for another source sharing the same database.
+    pub fn for_source(&self, source: &str) -> Self {
+        Self { conn: self.conn.clone(), source: source.into() }
+    }
+
+    /// Copies acknowledgement records from an older store, if this one has none yet.
+    pub fn import_history(&self, records: &[AckRecord]) -> Result<(), anyhow::Error> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if !Self::read_history(&tx, &self.source)?.is_empty() {
+            return Ok(());
+        }
+        for r in records {
+            Self::insert_record(&tx, r)?;
+        }
+        if let Some(v) = ack_history::effective(records).last() {
+            tx.execute("INSERT OR REPLACE INTO acknowledged (source, version) VALUES (?1, ?2)",
+                       params![self.source, v.to_string()])?;
+        }
+        tx.commit()?;
+
+        info!("Imported {} acknowledgement records for {}.", records.len(), self.source);
+        Ok(())
+    }
+
+    fn insert_record(conn: &Connection, record: &AckRecord) -> rusqlite::Result<usize> {
+        let action = match record.action {
+            AckAction::Acknowledged => "acknowledged",
+            AckAction::Undone => "undone"
+        };
+        conn.execute("INSERT INTO acks (source, action, version, timestamp, user, note) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                     params![record.source, action, record.version.to_string(), record.timestamp, record.user, record.note])
+    }
+
+    fn read_history(conn: &Connection, source: &str) -> rusqlite::Result<Vec<AckRecord>> {
+        let mut stmt = conn.prepare("SELECT action, version, source, timestamp, user, note FROM acks WHERE source = ?1 ORDER BY id")?;
+        let records = stmt.query_map(params![source], |r| {
+            let action = match r.get::<_, String>(0)?.as_str() {
+                "undone" => AckAction::Undone,
+                _ => AckAction::Acknowledged
+            };
+            Ok(AckRecord {
+                action,
+                version: BuildVersion::parse(&r.get::<_, String>(1)?).unwrap_or_default(),
+                source: r.get(2)?,
+                timestamp: r.get(3)?,
+                user: r.get(4)?,
+                note: r.get(5)?
+            })
+        })?.collect();
+        records
+    }
+
+    /// An app-wide setting changed at runtime, shared by every source.
+    pub fn setting(&self, key: &str) -> Option<String> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT value FROM settings WHERE key = ?1", params![key], |r| r.get(0)).optional() {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Unable to read setting {}. Error: {}", key, e);
+                None
+            }
+        }
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) {
+        let conn = self.conn.lock().unwrap();
+        if let Err(e) = conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![key, value]) {
+            error!("Unable to write setting {}. Error: {}", key, e);
+        }
+    }
+
+    fn get_state(&self, column: &str) -> BuildVersion {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM monitor_state WHERE source = ?1", column);
+        match conn.query_row(&sql, params![self.source], |r| r.get::<_, Option<String>>(0)).optional() {
+            Ok(Some(Some(v))) => BuildVersion::parse(&v).unwrap_or_default(),
+            Ok(_) => BuildVersion::default(),
+            Err(e) => {
+                error!("Unable to read {} for {}. Error: {}", column, self.source, e);
+                BuildVersion::default()
+            }
+        }
+    }
+
+    fn set_state(&self, column: &str, version: BuildVersion) {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("INSERT INTO monitor_state (source, {0}) VALUES (?1, ?2) \
+                           ON CONFLICT(source) DO UPDATE SET {0} = excluded.{0}", column);
+        if let Err(e) = conn.execute(&sql, params![self.source, version.to_string()]) {
+            error!("Unable to write {} for {}. Error: {}", column, self.source, e);
+        }
+    }
+}
+
+impl VersionUpdater for SqliteStateStore {
+    fn get_version(&self) -> BuildVersion {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT version FROM acknowledged WHERE source = ?1",
+                             params![self.source], |r| r.get::<_, String>(0)).optional() {
+            Ok(Some(v)) => BuildVersion::parse(&v).unwrap_or_default(),
+            Ok(None) => BuildVersion::default(),
+            Err(e) => {
+                error!("Unable to read acknowledged version for {}. Error: {}", self.source, e);
+                BuildVersion::default()
+            }
+        }
+    }
+
+    fn set_version(&self, version: BuildVersion, note: Option<String>) {
+        let record = AckRecord::new(AckAction::Acknowledged, version, &self.source, note);
+        let mut conn = self.conn.lock().unwrap();
+        let result = conn.transaction().and_then(|tx| {
+            tx.execute("INSERT OR REPLACE INTO acknowledged (source, version) VALUES (?1, ?2)",
+                       params![self.source, version.to_string()])?;
+            Self::insert_record(&tx, &record)?;
+            tx.commit()
+        });
+
+        match result {
+            Ok(_) => {
+                info!("Acknowledged {} for {}.", version, self.source);
+            }
+            Err(e) => {
+                error!("Unable to acknowledge {} for {}. Error: {}", version, self.source, e);
+            }
+        }
+    }
+
+    fn undo(&self) -> BuildVersion {
+        // Read and write in one transaction so two processes can't both undo the same entry.
+        let mut conn = self.conn.lock().unwrap();
+        let result = conn.transaction_with_behavior(TransactionBehavior::Immediate).and_then(|tx| {
+            let mut effective = ack_history::effective(&Self::read_history(&tx, &self.source)?);
+            let undone = match effective.pop() {
+                None => return Ok(None),
+                Some(v) => v
+            };
+            let current = effective.last().copied().unwrap_or_default();
+
+            if current == BuildVersion::default() {
+                tx.execute("DELETE FROM acknowledged WHERE source = ?1", params![self.source])?;
+            } else {
+                tx.execute("INSERT OR REPLACE INTO acknowledged (source, version) VALUES (?1, ?2)",
+                           params![self.source, current.to_string()])?;
+            }
+            Self::insert_record(&tx, &AckRecord::new(AckAction::Undone, undone, &self.source, None))?;
+            tx.commit()?;
+            Ok(Some((undone, current)))
+        });
+        drop(conn);
+
+        match result {
+            Ok(Some((undone, current))) => {
+                info!("Undid acknowledgement of {} for {}.", undone, self.source);
+                current
+            }
+            Ok(None) => {
+                info!("Nothing to undo for {}.", self.source);
+                BuildVersion::default()
+            }
+            Err(e) => {
+                error!("Failed to undo acknowledgement for {}. Error: {}", self.source, e);
+                self.get_version()
+            }
+        }
+    }
+
+    fn history(&self) -> Vec<AckRecord> {
+        let conn = self.conn.lock().unwrap();
+        match Self::read_history(&conn, &self.source) {
+            Ok(records) => records,
+            Err(e) => {
+                error!("Unable to read acknowledgement history for {}. Error: {}", self.source, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl DetectionStore for SqliteStateStore {
+    fn last_seen(&self) -> BuildVersion {
+        self.get_state("last_seen")
+    }
+
+    fn record_seen(&self, version: BuildVersion) {
+        {
+            let conn = self.conn.lock().unwrap();
+            if let Err(e) = conn.execute("INSERT OR IGNORE INTO detections (source, version, first_seen) VALUES (?1, ?2, ?3)",
+                                         params![self.source, version.to_string(), Utc::now()]) {
+                error!("Unable to record detection of {} for {}. Error: {}", version, self.source, e);
+            }
+        }
+        self.set_state("last_seen", version);
+    }
+
+    fn detections(&self) -> Vec<Detection> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.prepare("SELECT source, version, first_seen FROM detections WHERE source = ?1 ORDER BY first_seen, rowid")
+            .and_then(|mut stmt| {
+                stmt.query_map(params![self.source], |r| {
+                    Ok(Detection {
+                        source: r.get(0)?,
+                        version: BuildVersion::parse(&r.get::<_, String>(1)?).unwrap_or_default(),
+                        first_seen: r.get(2)?
+                    })
+                })?.collect()
+            });
+
+        match result {
+            Ok(detections) => detections,
+            Err(e) => {
+                error!("Unable to read detections for {}. Error: {}", self.source, e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn last_notified(&self) -> Option<(BuildVersion, DateTime<Utc>)> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.query_row("SELECT last_notified, last_notified_at FROM monitor_state WHERE source = ?1",
+                                    params![self.source], |r| Ok((r.get::<_, Option<String>>(0)?, r.get::<_, Option<DateTime<Utc>>>(1)?)))
+            .optional();
+        match result {
+            Ok(Some((Some(v), Some(at)))) => BuildVersion::parse(&v).ok().map(|v| (v, at)),
+            Ok(_) => None,
+            Err(e) => {
+                error!("Unable to read last_notified for {}. Error: {}", self.source, e);
+                None
+            }
+        }
+    }
+
+    fn set_last_notified(&self, version: BuildVersion, at: DateTime<Utc>) {
+        let conn = self.conn.lock().unwrap();
+        if let Err(e) = conn.execute("INSERT INTO monitor_state (source, last_notified, last_notified_at) VALUES (?1, ?2, ?3) \
+                                      ON CONFLICT(source) DO UPDATE SET last_notified = excluded.last_notified, last_notified_at = excluded.last_notified_at",
+                                     params![self.source, version.to_string(), at]) {
+            error!("Unable to write last_notified for {}. Error: {}", self.source, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use chrono::{TimeZone, Utc};
+    use tempfile::tempdir;
+    use crate::ack_history::{AckAction, AckRecord};
+    use crate::build_version::BuildVersion;
+    use crate::state_store::{DetectionStore, SqliteStateStore};
+    use crate::version_updater::VersionUpdater;
+
+    fn version(t: i32) -> BuildVersion {
+        BuildVersion { major: 1, minor: 2, patch: 3, t }
+    }
+
+    #[test]
+    fn acknowledges_and_undoes_per_source() {
+        let a = SqliteStateStore::open_in_memory("a").unwrap();
+        let b = a.for_source("b");
+
+        a.set_version(version(1), None);
+        a.set_version(version(2), Some(String::from("installed")));
+        b.set_version(version(9), None);
+
+        assert_eq!(a.undo(), version(1));
+        assert_eq!(a.get_version(), version(1));
+        assert_eq!(b.get_version(), version(9));
+        assert_eq!(a.history().len(), 3);
+        assert_eq!(a.history()[1].note.as_deref(), Some("installed"));
+    }
+
+    #[test]
+    fn state_survives_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        {
+            let store = SqliteStateStore::open(&path, "a").unwrap();
+            store.record_seen(version(1));
+            store.record_seen(version(2));
+            store.record_seen(version(1));
+            store.set_last_notified(version(2), Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap());
+            store.set_version(version(2), None);
+        }
+
+        let store = SqliteStateStore::open(&path, "a").unwrap();
+        assert_eq!(store.last_seen(), version(1));
+        assert_eq!(store.last_notified(), Some((version(2), Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap())));
+        assert_eq!(store.get_version(), version(2));
+        let versions: Vec<BuildVersion> = store.detections().iter().map(|d| d.version).collect();
+        assert_eq!(versions, vec![version(1), version(2)]);
+    }
+
+    #[test]
+    fn imports_history_once() {
+        let store = SqliteStateStore::open_in_memory("a").unwrap();
+        let records = vec![
+            AckRecord::new(AckAction::Acknowledged, version(1), "a", None),
+            AckRecord::new(AckAction::Acknowledged, version(2), "a", None),
+            AckRecord::new(AckAction::Undone, version(2), "a", None)
+        ];
+
+        store.import_history(&records).unwrap();
+        store.import_history(&records).unwrap();
+
+        assert_eq!(store.get_version(), version(1));
+        assert_eq!(store.history().len(), 3);
+    }
+
+    #[test]
+    fn concurrent_undos_undo_different_entries() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        let store = SqliteStateStore::open(&path, "a").unwrap();
+        for t in 1..=3 {
+            store.set_version(version(t), None);
+        }
+
+        let undos: Vec<_> = (0..2).map(|_| {
+            let path = path.clone();
+            thread::spawn(move || SqliteStateStore::open(&path, "a").unwrap().undo())
+        }).collect();
+        let mut results: Vec<BuildVersion> = undos.into_iter().map(|h| h.join().unwrap()).collect();
+        results.sort_by_key(|v| v.t);
+
+        assert_eq!(results, vec![version(1), version(2)]);
+        assert_eq!(store.get_version(), version(1));
+    }
+}
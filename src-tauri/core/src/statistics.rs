@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use crate::ack_history::{AckAction, AckRecord};
+use crate::build_version::BuildVersion;
+use crate::state_store::Detection;
+
+/// A detected version and when (if ever) it was acknowledged.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReleaseEntry {
+    pub version: BuildVersion,
+    pub first_seen: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub time_to_acknowledge_seconds: Option<i64>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub builds: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReleaseStatistics {
+    pub source: String,
+    pub total_builds: usize,
+    pub builds_per_day: Vec<DailyCount>,
+    pub average_builds_per_day: f64,
+    pub median_time_to_acknowledge_seconds: Option<i64>,
+    pub median_gap_seconds: Option<i64>,
+    pub longest_gap_seconds: Option<i64>,
+    pub seconds_since_last_build: Option<i64>
+}
+
+fn median(mut values: Vec<i64>) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        Some((values[mid - 1] + values[mid]) / 2)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Pairs every detection with the first acknowledgement of that version made after it was seen.
+pub fn release_history(detections: &[Detection], acks: &[AckRecord]) -> Vec<ReleaseEntry> {
+    let mut entries: Vec<ReleaseEntry> = detections.iter().map(|d| {
+        let acknowledged_at = acks.iter()
+            .filter(|a| a.action == AckAction::Acknowledged && a.version == d.version && a.timestamp >= d.first_seen)
+            .map(|a| a.timestamp)
+            .min();
+
+        ReleaseEntry {
+            version: d.version,
+            first_seen: d.first_seen,
+            acknowledged_at,
+            time_to_acknowledge_seconds: acknowledged_at.map(|t| (t - d.first_seen).num_seconds())
+        }
+    }).collect();
+
+    entries.sort_by_key(|e| e.first_seen);
+    entries
+}
+
+/// Aggregates a source's history. Days are bucketed in `tz`.
+pub fn release_statistics<Tz: TimeZone>(source: &str, history: &[ReleaseEntry], now: DateTime<Utc>, tz: &Tz) -> ReleaseStatistics {
+    let mut per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for e in history {
+        *per_day.entry(e.first_seen.with_timezone(tz).date_naive()).or_default() += 1;
+    }
+
+    let average_builds_per_day = match (per_day.keys().next(), per_day.keys().last()) {
+        (Some(first), Some(last)) => history.len() as f64 / ((*last - *first).num_days() + 1) as f64,
+        _ => 0.0
+    };
+
+    let gaps: Vec<i64> = history.windows(2)
+        .map(|w| (w[1].first_seen - w[0].first_seen).num_seconds())
+        .collect();
+
+    ReleaseStatistics {
+        source: source.into(),
+        total_builds: history.len(),
+        builds_per_day: per_day.into_iter().map(|(date, builds)| DailyCount { date, builds }).collect(),
+        average_builds_per_day,
+        median_time_to_acknowledge_seconds: median(history.iter().filter_map(|e| e.time_to_acknowledge_seconds).collect()),
+        median_gap_seconds: median(gaps.clone()),
+        longest_gap_seconds: gaps.into_iter().max(),
+        seconds_since_last_build: history.last().map(|e| (now - e.first_seen).num_seconds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+    use crate::ack_history::{AckAction, AckRecord};
+    use crate::build_version::BuildVersion;
+    use crate::state_store::Detection;
+    use crate::statistics::{release_history, release_statistics};
+
+    fn version(t: i32) -> BuildVersion {
+        BuildVersion { major: 1, minor: 2, patch: 3, t }
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, day, hour, 0, 0).unwrap()
+    }
+
+    fn detection(t: i32, first_seen: DateTime<Utc>) -> Detection {
+        Detection { source: String::from("a"), version: version(t), first_seen }
+    }
+
+    fn ack(t: i32, timestamp: DateTime<Utc>) -> AckRecord {
+        AckRecord { timestamp, ..AckRecord::new(AckAction::Acknowledged, version(t), "a", None) }
+    }
+
+    #[test]
+    fn computes_history_and_aggregates() {
+        let detections = vec![detection(1, at(1, 9)), detection(2, at(1, 15)), detection(3, at(3, 9))];
+        let acks = vec![ack(1, at(1, 10)), ack(3, at(3, 13)), ack(2, at(1, 14))];
+
+        let history = release_history(&detections, &acks);
+        assert_eq!(history[0].time_to_acknowledge_seconds, Some(3600));
+        assert_eq!(history[1].acknowledged_at, None);
+        assert_eq!(history[2].time_to_acknowledge_seconds, Some(4 * 3600));
+
+        let stats = release_statistics("a", &history, at(4, 9), &Utc);
+        assert_eq!(stats.total_builds, 3);
+        assert_eq!(stats.builds_per_day.len(), 2);
+        assert_eq!(stats.builds_per_day[0].date, NaiveDate::from_ymd_opt(2024, 7, 1).unwrap());
+        assert_eq!(stats.builds_per_day[0].builds, 2);
+        assert_eq!(stats.average_builds_per_day, 1.0);
+        assert_eq!(stats.median_time_to_acknowledge_seconds, Some(9000));
+        assert_eq!(stats.median_gap_seconds, Some((Duration::hours(6) + Duration::hours(42)).num_seconds() / 2));
+        assert_eq!(stats.longest_gap_seconds, Some(42 * 3600));
+        assert_eq!(stats.seconds_since_last_build, Some(24 * 3600));
+    }
+
+    #[test]
+    fn empty_history_has_no_aggregates() {
+        let stats = release_statistics("a", &[], at(1, 0), &Utc);
+        assert_eq!(stats.total_builds, 0);
+        assert_eq!(stats.median_gap_seconds, None);
+        assert_eq!(stats.seconds_since_last_build, None);
+    }
+}
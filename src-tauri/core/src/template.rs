@@ -0,0 +1,31 @@
+/// Replaces `{name}` placeholders with their values. Unknown placeholders are left as they are.
+pub fn render(template: &str, vars: &[(&str, String)]) -> String {
+    let mut out = template.to_string();
+    for (name, value) in vars {
+        out = out.replace(&format!("{{{}}}", name), value);
+    }
+    out
+}
+
+/// Like `render`, but escapes values so they can sit inside JSON strings.
+pub fn render_json(template: &str, vars: &[(&str, String)]) -> String {
+    let escaped: Vec<(&str, String)> = vars.iter()
+        .map(|(name, value)| {
+            let quoted = serde_json::to_string(value).unwrap_or_default();
+            (*name, quoted[1..quoted.len() - 1].to_string())
+        })
+        .collect();
+    render(template, &escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{render, render_json};
+
+    #[test]
+    fn replaces_known_placeholders() {
+        let vars = [("version", String::from("R1.02.03T04")), ("path", String::from(r#"C:\builds\"new".zip"#))];
+        assert_eq!(render("{version} at {path} {other}", &vars), r#"R1.02.03T04 at C:\builds\"new".zip {other}"#);
+        assert_eq!(render_json(r#"{"path": "{path}"}"#, &vars), r#"{"path": "C:\\builds\\\"new\".zip"}"#);
+    }
+}
@@ -0,0 +1,224 @@
+//! In-memory stand-ins for the monitor's collaborators.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use tiny_http::Server;
+use crate::ack_history::{AckAction, AckRecord};
+use crate::build_version::BuildVersion;
+use crate::clock::Clock;
+use crate::version_checker::{UnreachableSourceError, VersionChecker};
+use crate::version_updater::VersionUpdater;
+
+struct ManualTime {
+    now: DateTime<Utc>,
+    sleepers: Vec<DateTime<Utc>>
+}
+
+/// A clock that only moves when told to. `sleep` blocks until enough virtual time has passed.
+pub struct ManualClock {
+    time: Mutex<ManualTime>,
+    changed: Condvar
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { time: Mutex::new(ManualTime { now, sleepers: Vec::new() }), changed: Condvar::new() }
+    }
+
+    /// Blocks until some thread is sleeping on this clock.
+    pub fn wait_for_sleeper(&self) {
+        let time = self.time.lock().unwrap();
+        let _time = self.changed.wait_while(time, |t| t.sleepers.is_empty()).unwrap();
+    }
+
+    /// Moves time forward and waits for every sleeper that is now due to wake up.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.time.lock().unwrap();
+        time.now += chrono::Duration::from_std(duration).unwrap();
+        self.changed.notify_all();
+        let _time = self.changed.wait_while(time, |t| t.sleepers.iter().any(|d| *d <= t.now)).unwrap();
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.time.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut time = self.time.lock().unwrap();
+        let deadline = time.now + chrono::Duration::from_std(duration).unwrap();
+        time.sleepers.push(deadline);
+        self.changed.notify_all();
+
+        let mut time = self.changed.wait_while(time, |t| t.now < deadline).unwrap();
+        let i = time.sleepers.iter().position(|d| *d == deadline).unwrap();
+        time.sleepers.remove(i);
+        self.changed.notify_all();
+    }
+}
+
+enum Response {
+    Version(BuildVersion),
+    Failure(String),
+    Unreachable(String)
+}
+
+pub struct FakeVersionChecker {
+    response: Mutex<Response>
+}
+
+impl Default for FakeVersionChecker {
+    fn default() -> Self {
+        Self { response: Mutex::new(Response::Version(BuildVersion::default())) }
+    }
+}
+
+impl FakeVersionChecker {
+    pub fn set_version(&self, version: BuildVersion) {
+        *self.response.lock().unwrap() = Response::Version(version);
+    }
+
+    pub fn fail(&self, error: &str) {
+        *self.response.lock().unwrap() = Response::Failure(error.into());
+    }
+
+    pub fn unreachable(&self, location: &str) {
+        *self.response.lock().unwrap() = Response::Unreachable(location.into());
+    }
+}
+
+impl VersionChecker for FakeVersionChecker {
+    fn get_latest_version(&self) -> Result<BuildVersion, anyhow::Error> {
+        match &*self.response.lock().unwrap() {
+            Response::Version(v) => Ok(*v),
+            Response::Failure(e) => Err(anyhow!(e.clone())),
+            Response::Unreachable(location) => Err(UnreachableSourceError {
+                location: location.clone(),
+                error: std::io::Error::from(std::io::ErrorKind::NotFound)
+            }.into())
+        }
+    }
+}
+
+pub struct FakeVersionUpdater {
+    source: String,
+    acked: Mutex<Vec<BuildVersion>>,
+    history: Mutex<Vec<AckRecord>>
+}
+
+impl FakeVersionUpdater {
+    pub fn new(source: &str) -> Self {
+        Self { source: source.into(), acked: Mutex::new(Vec::new()), history: Mutex::new(Vec::new()) }
+    }
+}
+
+impl VersionUpdater for FakeVersionUpdater {
+    fn get_version(&self) -> BuildVersion {
+        self.acked.lock().unwrap().last().copied().unwrap_or_default()
+    }
+
+    fn set_version(&self, version: BuildVersion, note: Option<String>) {
+        self.acked.lock().unwrap().push(version);
+        self.history.lock().unwrap().push(AckRecord::new(AckAction::Acknowledged, version, &self.source, note));
+    }
+
+    fn undo(&self) -> BuildVersion {
+        let mut acked = self.acked.lock().unwrap();
+        if let Some(undone) = acked.pop() {
+            self.history.lock().unwrap().push(AckRecord::new(AckAction::Undone, undone, &self.source, None));
+        }
+        acked.last().copied().unwrap_or_default()
+    }
+
+    fn history(&self) -> Vec<AckRecord> {
+        self.history.lock().unwrap().clone()
+    }
+}
+
+/// A request seen by `http_receiver`.
+pub struct ReceivedRequest {
+    pub method: String,
+    pub body: String,
+    pub headers: Vec<(String, String)>
+}
+
+impl ReceivedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Answers with the given statuses in turn and reports each request it received. Returns the URL
+/// to send to.
+pub fn http_receiver(statuses: Vec<u16>) -> (String, mpsc::Receiver<ReceivedRequest>) {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", server.server_addr());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for status in statuses {
+            let mut request = server.recv().unwrap();
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let headers = request.headers().iter().map(|h| (h.field.to_string(), h.value.to_string())).collect();
+            tx.send(ReceivedRequest { method: request.method().to_string(), body, headers }).unwrap();
+            request.respond(tiny_http::Response::empty(status)).unwrap();
+        }
+    });
+    (url, rx)
+}
+
+/// A mail seen by `smtp_sink`.
+pub struct ReceivedMail {
+    pub commands: Vec<String>,
+    pub data: String
+}
+
+/// A plaintext SMTP server which accepts one connection, takes any credentials and reports the
+/// mail it was given. Returns the port to send to.
+pub fn smtp_sink() -> (u16, mpsc::Receiver<ReceivedMail>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut mail = ReceivedMail { commands: Vec::new(), data: String::new() };
+        writer.write_all(b"220 sink ESMTP\r\n").unwrap();
+
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let command = line.trim_end().to_string();
+            line.clear();
+            let verb = command.split(' ').next().unwrap_or_default().to_uppercase();
+            mail.commands.push(command);
+            let reply: &[u8] = match verb.as_str() {
+                "EHLO" => b"250-sink\r\n250 AUTH PLAIN LOGIN\r\n",
+                "AUTH" => b"235 Authenticated\r\n",
+                "DATA" => {
+                    writer.write_all(b"354 Go ahead\r\n").unwrap();
+                    while reader.read_line(&mut line).unwrap_or(0) > 0 && line != ".\r\n" {
+                        mail.data.push_str(&line);
+                        line.clear();
+                    }
+                    line.clear();
+                    b"250 Queued\r\n"
+                }
+                "QUIT" => {
+                    let _ = writer.write_all(b"221 Bye\r\n");
+                    break;
+                }
+                _ => b"250 OK\r\n"
+            };
+            writer.write_all(reply).unwrap();
+        }
+        let _ = tx.send(mail);
+    });
+    (port, rx)
+}
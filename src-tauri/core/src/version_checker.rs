@@ -0,0 +1,169 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use tracing::error;
+use crate::build_version::BuildVersion;
+use crate::release::DetectedRelease;
+
+/// The source itself couldn't be reached, as opposed to a failure while scanning it.
+#[derive(Debug)]
+pub struct UnreachableSourceError {
+    pub location: String,
+    pub error: std::io::Error
+}
+
+impl Display for UnreachableSourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unable to reach {}. Error: {}", self.location, self.error)
+    }
+}
+
+impl std::error::Error for UnreachableSourceError {}
+
+pub trait VersionChecker {
+    fn get_latest_version(&self) -> Result<BuildVersion, anyhow::Error>;
+
+    /// The latest version together with what it was found in. Checkers which only know the
+    /// version report just that.
+    fn get_latest_release(&self, source: &str) -> Result<DetectedRelease, anyhow::Error> {
+        Ok(DetectedRelease::new(source, self.get_latest_version()?))
+    }
+}
+
+pub struct SharedFolderVersionChecker {
+    path: String,
+    file_regex: Regex
+}
+
+impl SharedFolderVersionChecker {
+    pub fn new(path: &str, file_regex: &str) -> Self {
+        Self { path: path.into(), file_regex: Regex::new(file_regex).unwrap() }
+    }
+
+    /// The newest matching file and its version.
+    fn find_latest(&self) -> Result<(BuildVersion, Option<PathBuf>), Error> {
+        let mut latest_version = BuildVersion::default();
+        let mut latest_path = None;
+        match fs::read_dir(self.path.as_str()) {
+            Ok(directory) => {
+                for file in directory {
+                    let f = match file {
+                        Ok(f) => f,
+                        Err(e) => {
+                            error!("Unable to read an entry of {}. Error: {}.", self.path, e);
+                            continue;
+                        }
+                    };
+                    let filename = f.file_name().to_string_lossy().to_string();
+                    if !self.file_regex.is_match(&filename) {
+                        continue;
+                    }
+                    match BuildVersion::parse(&filename) {
+                        Ok(version) => {
+                            if version.major > latest_version.major {
+                                latest_version = version;
+                                latest_path = Some(f.path());
+                                continue;
+                            }
+
+                            if version.major == latest_version.major &&
+                                version.minor > latest_version.minor {
+                                latest_version = version;
+                                latest_path = Some(f.path());
+                                continue;
+                            }
+
+                            if version.major == latest_version.major &&
+                                version.minor == latest_version.minor &&
+                                version.patch > latest_version.patch {
+                                latest_version = version;
+                                latest_path = Some(f.path());
+                                continue;
+                            }
+
+                            if version.major == latest_version.major &&
+                                version.minor == latest_version.minor &&
+                                version.patch == latest_version.patch &&
+                                version.t > latest_version.t {
+                                latest_version = version;
+                                latest_path = Some(f.path());
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to parse {}. Error: {}.", filename, e);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Unable to read directory {}. Error: {}.", self.path, e);
+                return Err(UnreachableSourceError { location: self.path.clone(), error: e }.into());
+            }
+        }
+
+        Ok((latest_version, latest_path))
+    }
+}
+
+impl VersionChecker for SharedFolderVersionChecker {
+    fn get_latest_version(&self) -> Result<BuildVersion, Error> {
+        Ok(self.find_latest()?.0)
+    }
+
+    fn get_latest_release(&self, source: &str) -> Result<DetectedRelease, Error> {
+        let (version, path) = self.find_latest()?;
+        let mut release = DetectedRelease::new(source, version);
+        if let Some(p) = path {
+            let metadata = fs::metadata(&p).ok();
+            release.path = Some(p.display().to_string());
+            release.raw_name = p.file_name().map(|n| n.to_string_lossy().to_string());
+            release.size = metadata.as_ref().map(|m| m.len());
+            release.modified = metadata.and_then(|m| m.modified().ok()).map(DateTime::<Utc>::from);
+        }
+        Ok(release)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::build_version::BuildVersion;
+    use crate::version_checker::{SharedFolderVersionChecker, VersionChecker};
+
+    #[test]
+    fn reports_the_file_behind_the_latest_version() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("R1.02.03T04.zip"), b"new").unwrap();
+        fs::write(dir.path().join("R1.02.03T03.zip"), b"older").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"ignored").unwrap();
+
+        let checker = SharedFolderVersionChecker::new(&dir.path().to_string_lossy(), r".*\.zip");
+        let release = checker.get_latest_release("a").unwrap();
+        assert_eq!(release.source, "a");
+        assert_eq!(release.version, BuildVersion::parse("R1.02.03T04").unwrap());
+        assert_eq!(release.raw_name.as_deref(), Some("R1.02.03T04.zip"));
+        assert_eq!(release.path, Some(dir.path().join("R1.02.03T04.zip").display().to_string()));
+        assert_eq!(release.size, Some(3));
+        assert!(release.modified.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_names_which_are_not_utf8_and_fails_on_a_missing_folder() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("R1.02.03T04.zip"), b"new").unwrap();
+        fs::write(dir.path().join(OsStr::from_bytes(b"R1.02.03T05\xff.zip")), b"newer").unwrap();
+
+        let checker = SharedFolderVersionChecker::new(&dir.path().to_string_lossy(), r".*\.zip");
+        assert_eq!(checker.get_latest_version().unwrap(), BuildVersion::parse("R1.02.03T05").unwrap());
+
+        let missing = SharedFolderVersionChecker::new(&dir.path().join("gone").to_string_lossy(), r".*\.zip");
+        assert!(missing.get_latest_version().is_err());
+    }
+}
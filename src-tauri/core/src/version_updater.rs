@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use fs2::FileExt;
+use tracing::{error, info, warn};
+use crate::ack_history::{AckAction, AckHistory, AckRecord};
+use crate::build_version::BuildVersion;
+
+pub trait VersionUpdater{
+    fn get_version(&self) -> BuildVersion;
+    fn set_version(&self, version: BuildVersion, note: Option<String>);
+    /// Reverts the last acknowledgement and returns the version acknowledged afterwards.
+    fn undo(&self) -> BuildVersion;
+    fn history(&self) -> Vec<AckRecord>;
+}
+
+/// Acknowledged versions keyed by source, stored as JSON.
+///
+/// Writes go to a temp file which is renamed over the store, a copy is kept as `.bak`, and every
+/// read-modify-write happens under an exclusive lock on a sibling `.lock` file. A store which
+/// can't be parsed is moved aside as `.corrupt` and recovered from the backup. Every change is
+/// also recorded in an `ack_history.jsonl` audit log next to the store.
+pub struct FileCacheVersionUpdater {
+    path: PathBuf,
+    source: String,
+    history: AckHistory
+}
+
+impl FileCacheVersionUpdater {
+    pub fn new(path: &Path, source: &str) -> Self {
+        Self {
+            path: path.into(),
+            source: source.into(),
+            history: AckHistory::new(&path.with_file_name("ack_history.jsonl"))
+        }
+    }
+
+    fn sibling(&self, extension: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(extension);
+        self.path.with_file_name(name)
+    }
+
+    fn lock(&self) -> std::io::Result<File> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let lock_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.sibling("lock"))?;
+        lock_file.lock_exclusive()?;
+        Ok(lock_file)
+    }
+
+    fn parse(path: &Path) -> Option<BTreeMap<String, String>> {
+        let s = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&s).ok()
+    }
+
+    /// Reads the store, recovering from the backup if it is corrupt. Must be called with the lock held.
+    fn read_acks(&self) -> BTreeMap<String, String> {
+        if !self.path.exists() {
+            return BTreeMap::new();
+        }
+
+        if let Some(acks) = Self::parse(&self.path) {
+            return acks;
+        }
+
+        let corrupt = self.sibling("corrupt");
+        warn!("Ack store {} is corrupt, moving it to {}.", self.path.display(), corrupt.display());
+        if let Err(e) = fs::rename(&self.path, &corrupt) {
+            error!("Unable to move corrupt ack store {}. Error: {}", self.path.display(), e);
+        }
+
+        match Self::parse(&self.sibling("bak")) {
+            Some(acks) => {
+                info!("Recovered ack store {} from backup.", self.path.display());
+                acks
+            }
+            None => BTreeMap::new()
+        }
+    }
+
+    fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        let tmp = path.with_file_name(name);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Writes the store and its backup. Must be called with the lock held.
+    fn write_acks(&self, acks: &BTreeMap<String, String>) -> Result<(), anyhow::Error> {
+        let contents = serde_json::to_vec_pretty(acks)?;
+        Self::write_atomic(&self.path, &contents)?;
+        Self::write_atomic(&self.sibling("bak"), &contents)?;
+        Ok(())
+    }
+}
+
+impl VersionUpdater for FileCacheVersionUpdater {
+    fn get_version(&self) -> BuildVersion {
+        let acks = match self.lock() {
+            Ok(_lock) => self.read_acks(),
+            Err(e) => {
+                error!("Unable to lock {}. Error: {}", self.path.display(), e);
+                return BuildVersion::default();
+            }
+        };
+
+        match acks.get(&self.source) {
+            None => {}
+            Some(s) => {
+                match BuildVersion::parse(s) {
+                    Ok(v) => {
+                        return v;
+                    }
+                    Err(e) => {
+                        error!("Unable to parse version {}. Error: {}", s, e);
+                    }
+                }
+            }
+        }
+
+        BuildVersion::default()
+    }
+
+    fn set_version(&self, version: BuildVersion, note: Option<String>) {
+        let result = self.lock().map_err(anyhow::Error::from).and_then(|_lock| {
+            let mut acks = self.read_acks();
+            acks.insert(self.source.clone(), version.to_string());
+            self.write_acks(&acks)?;
+            self.history.append(&AckRecord::new(AckAction::Acknowledged, version, &self.source, note))
+        });
+
+        match result {
+            Ok(_) => {
+                info!("Wrote {} for {} to {}.", version, self.source, self.path.display());
+            }
+            Err(e) => {
+                error!("Unable to write version to {}. Error: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    fn undo(&self) -> BuildVersion {
+        let _lock = match self.lock() {
+            Ok(l) => l,
+            Err(e) => {
+                error!("Unable to lock {}. Error: {}", self.path.display(), e);
+                return self.get_version();
+            }
+        };
+
+        let mut effective = self.history.effective(&self.source);
+        let undone = match effective.pop() {
+            None => {
+                info!("Nothing to undo for {}.", self.source);
+                return BuildVersion::default();
+            }
+            Some(v) => v
+        };
+        let current = effective.last().copied().unwrap_or_default();
+
+        let mut acks = self.read_acks();
+        if current == BuildVersion::default() {
+            acks.remove(&self.source);
+        } else {
+            acks.insert(self.source.clone(), current.to_string());
+        }
+
+        let result = self.write_acks(&acks).and_then(|_| {
+            self.history.append(&AckRecord::new(AckAction::Undone, undone, &self.source, None))
+        });
+        match result {
+            Ok(_) => {
+                info!("Undid acknowledgement of {} for {}.", undone, self.source);
+            }
+            Err(e) => {
+                error!("Failed to undo acknowledgement in {}. Error: {}", self.path.display(), e);
+            }
+        }
+
+        current
+    }
+
+    fn history(&self) -> Vec<AckRecord> {
+        self.history.for_source(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::tempdir;
+    use crate::build_version::BuildVersion;
+    use crate::ack_history::AckAction;
+    use crate::version_updater::{FileCacheVersionUpdater, VersionUpdater};
+
+    fn version(t: i32) -> BuildVersion {
+        BuildVersion { major: 1, minor: 2, patch: 3, t }
+    }
+
+    #[test]
+    fn keeps_versions_per_source() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("acks.json");
+        let a = FileCacheVersionUpdater::new(&path, "a");
+        let b = FileCacheVersionUpdater::new(&path, "b");
+
+        a.set_version(version(1), None);
+        b.set_version(version(2), None);
+        a.undo();
+
+        assert_eq!(a.get_version(), BuildVersion::default());
+        assert_eq!(b.get_version(), version(2));
+    }
+
+    #[test]
+    fn undo_restores_previous_acknowledgement() {
+        let dir = tempdir().unwrap();
+        let updater = FileCacheVersionUpdater::new(&dir.path().join("acks.json"), "a");
+
+        updater.set_version(version(1), None);
+        updater.set_version(version(2), Some(String::from("oops")));
+
+        assert_eq!(updater.undo(), version(1));
+        assert_eq!(updater.get_version(), version(1));
+
+        let actions: Vec<AckAction> = updater.history().iter().map(|r| r.action).collect();
+        assert_eq!(actions, vec![AckAction::Acknowledged, AckAction::Acknowledged, AckAction::Undone]);
+    }
+
+    #[test]
+    fn shorter_version_replaces_longer_one() {
+        let dir = tempdir().unwrap();
+        let updater = FileCacheVersionUpdater::new(&dir.path().join("acks.json"), "a");
+
+        updater.set_version(BuildVersion { major: 10, minor: 20, patch: 30, t: 40 }, None);
+        updater.set_version(version(1), None);
+
+        assert_eq!(updater.get_version(), version(1));
+    }
+
+    #[test]
+    fn concurrent_writers_do_not_lose_updates() {
+        let dir = tempdir().unwrap();
+        let path = Arc::new(dir.path().join("acks.json"));
+
+        let handles: Vec<_> = (0..8).map(|i| {
+            let path = path.clone();
+            thread::spawn(move || {
+                let updater = FileCacheVersionUpdater::new(&path, &format!("source-{}", i));
+                for t in 0..20 {
+                    updater.set_version(version(t), None);
+                }
+            })
+        }).collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        for i in 0..8 {
+            let updater = FileCacheVersionUpdater::new(&path, &format!("source-{}", i));
+            assert_eq!(updater.get_version(), version(19));
+        }
+    }
+
+    #[test]
+    fn recovers_truncated_store_from_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("acks.json");
+        let updater = FileCacheVersionUpdater::new(&path, "a");
+        updater.set_version(version(7), None);
+
+        let contents = fs::read(&path).unwrap();
+        fs::write(&path, &contents[..contents.len() / 2]).unwrap();
+
+        assert_eq!(updater.get_version(), version(7));
+        assert!(dir.path().join("acks.json.corrupt").exists());
+    }
+
+    #[test]
+    fn recovers_garbage_store_without_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("acks.json");
+        fs::write(&path, "R1.02.03T04 garbage").unwrap();
+        let updater = FileCacheVersionUpdater::new(&path, "a");
+
+        assert_eq!(updater.get_version(), BuildVersion::default());
+
+        updater.set_version(version(5), None);
+        assert_eq!(updater.get_version(), version(5));
+    }
+}
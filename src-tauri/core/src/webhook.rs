@@ -0,0 +1,166 @@
+use std::thread;
+use std::time::Duration;
+use anyhow::anyhow;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::warn;
+use crate::health::Backoff;
+use crate::notifier::Notifier;
+use crate::release::DetectedRelease;
+use crate::template;
+
+pub const SIGNATURE_HEADER: &str = "X-Release-Monitor-Signature";
+
+const DEFAULT_BODY: &str = r#"{"source": "{source}", "version": "{version}", "path": "{path}"}"#;
+
+fn default_method() -> String {
+    String::from("POST")
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+/// An outgoing webhook.
+///
+/// ```toml
+/// [[webhooks]]
+/// url = "https://ci.example.com/hooks/release"
+/// body = '{"text": "{version} is out on {source}"}'
+/// secret = "shared secret"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// JSON body with `{version}`, `{source}` and `{path}` placeholders.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Signs the body with HMAC-SHA256, sent as `sha256=<hex>` in the signature header.
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32
+}
+
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+    backoff: Backoff,
+    agent: ureq::Agent
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self {
+            config,
+            backoff: Backoff { initial: Duration::from_secs(1), max: Duration::from_secs(30) },
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build()
+        }
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sends `body`, retrying server errors and throttling with backoff.
+    pub fn deliver(&self, body: &str) -> Result<(), anyhow::Error> {
+        let mut attempt = 0;
+        loop {
+            let error = match self.send(body).map_err(|e| *e) {
+                Ok(_) => return Ok(()),
+                // Only server errors and throttling are worth retrying.
+                Err(ureq::Error::Status(code, _)) if code < 500 && code != 429 => {
+                    return Err(anyhow!("{} answered {}.", self.config.url, code));
+                }
+                Err(e) => e
+            };
+
+            attempt += 1;
+            if attempt > self.config.retries {
+                return Err(error.into());
+            }
+            let delay = self.backoff.delay(attempt);
+            warn!("Webhook {} failed, retrying in {:?}. Error: {}", self.config.url, delay, error);
+            thread::sleep(delay);
+        }
+    }
+
+    fn send(&self, body: &str) -> Result<(), Box<ureq::Error>> {
+        let mut request = self.agent.request(&self.config.method, &self.config.url)
+            .set("Content-Type", "application/json");
+        if let Some(secret) = &self.config.secret {
+            request = request.set(SIGNATURE_HEADER, &sign(secret, body));
+        }
+        request.send_string(body).map_err(Box::new)?;
+        Ok(())
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.config.url
+    }
+
+    fn notify(&self, release: &DetectedRelease) -> Result<(), anyhow::Error> {
+        let body = template::render_json(self.config.body.as_deref().unwrap_or(DEFAULT_BODY), &release.vars());
+        self.deliver(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::build_version::BuildVersion;
+    use crate::health::Backoff;
+    use crate::notifier::Notifier;
+    use crate::release::DetectedRelease;
+    use crate::testing::http_receiver;
+    use crate::webhook::{sign, WebhookConfig, WebhookNotifier, SIGNATURE_HEADER};
+
+    fn release() -> DetectedRelease {
+        DetectedRelease { source: String::from("a"), version: BuildVersion::parse("R1.02.03T04").unwrap(), path: Some(String::from(r"\\share\R1.02.03T04.zip")), ..Default::default() }
+    }
+
+    fn webhook(url: &str) -> WebhookNotifier {
+        let config = WebhookConfig {
+            url: url.into(),
+            method: String::from("PUT"),
+            body: Some(String::from(r#"{"text": "{version} on {source} at {path}"}"#)),
+            secret: Some(String::from("secret")),
+            retries: 2
+        };
+        WebhookNotifier::new(config).with_backoff(Backoff { initial: Duration::from_millis(10), max: Duration::from_millis(50) })
+    }
+
+    #[test]
+    fn sends_a_signed_templated_body_and_retries_server_errors() {
+        let (url, rx) = http_receiver(vec![503, 200]);
+        webhook(&url).notify(&release()).unwrap();
+
+        let first = rx.recv().unwrap();
+        assert_eq!(first.method, "PUT");
+        assert_eq!(first.body, r#"{"text": "R1.02.03T04 on a at \\\\share\\R1.02.03T04.zip"}"#);
+        assert_eq!(first.header(SIGNATURE_HEADER), Some(sign("secret", &first.body).as_str()));
+        assert_eq!(rx.recv().unwrap().body, first.body);
+    }
+
+    #[test]
+    fn gives_up_on_client_errors_and_after_the_last_retry() {
+        let (url, rx) = http_receiver(vec![404]);
+        assert!(webhook(&url).notify(&release()).is_err());
+        assert_eq!(rx.iter().count(), 1);
+
+        let (url, rx) = http_receiver(vec![500, 500, 500]);
+        assert!(webhook(&url).notify(&release()).is_err());
+        assert_eq!(rx.iter().count(), 3);
+    }
+}
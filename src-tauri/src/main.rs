@@ -1,41 +1,56 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod release_monitor;
-mod build_version;
-mod version_checker;
-mod publisher;
-mod version_updater;
-mod config;
-
 use std::{env, fs, thread};
-use std::any::Any;
-use std::collections::HashMap;
 use std::fs::{metadata, OpenOptions};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use anyhow::Error;
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
-use figment::{Figment, Provider};
-use figment::providers::{Format, Toml};
 use serde::Serialize;
 use tauri::{Manager, Window, SystemTray, SystemTrayMenu, SystemTrayEvent, CustomMenuItem, SystemTrayMenuItem, State};
+#[cfg(not(all(unix, not(target_os = "macos"))))]
 use tauri::api::notification::Notification;
-use crate::build_version::BuildVersion;
-use crate::config::VersionCheckerConfig;
-use crate::release_monitor::ReleaseMonitor;
-use crate::publisher::{Event, Subscription};
-use crate::version_checker::{SharedFolderVersionChecker, VersionChecker};
-use crate::version_updater::{FileCacheVersionUpdater, VersionUpdater};
+use clap::Parser;
+use release_monitor_core::ack_history::AckRecord;
+use release_monitor_core::build_version::BuildVersion;
+use release_monitor_core::chat::{ChatNotifier, DEDUP_DIR};
+use release_monitor_core::cli;
+use release_monitor_core::command_hook::CommandNotifier;
+use release_monitor_core::cli::Cli;
+use release_monitor_core::config;
+#[cfg(unix)]
+use release_monitor_core::daemon::DaemonClient;
+use release_monitor_core::download::ArtifactDownloader;
+use release_monitor_core::email::EmailNotifier;
+use release_monitor_core::gate::NotificationGate;
+use release_monitor_core::config::{APP_CONFIG_FILE, ConfigEntry, OrgConfig, OrgConfigCache, VersionCheckerConfig};
+use release_monitor_core::release::DetectedRelease;
+use release_monitor_core::release_monitor::ReleaseMonitor;
+use release_monitor_core::publisher::{ConfigReloaded, Event, Message, SubscriberMetrics, SubscriptionHandle};
+use release_monitor_core::version_checker::{SharedFolderVersionChecker, VersionChecker};
+use release_monitor_core::health::{HealthStatus, SourceHealth};
+use release_monitor_core::http_api::HttpApi;
+use release_monitor_core::notification::{DesktopNotifier, NotificationAction, NotificationText};
+use release_monitor_core::notifier::NotifierHub;
+use release_monitor_core::services::AppServices;
+use release_monitor_core::state_store::SqliteStateStore;
+use release_monitor_core::statistics::{ReleaseEntry, ReleaseStatistics};
+use release_monitor_core::version_updater::{FileCacheVersionUpdater, VersionUpdater};
+use release_monitor_core::webhook::WebhookNotifier;
 use std::string::String;
 use auto_launch::{AutoLaunch, AutoLaunchBuilder};
 use directories::ProjectDirs;
 use tracing::{error, info};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 
+/// Keeps the app's event subscriptions alive for as long as the app runs.
+struct Subscriptions(Vec<SubscriptionHandle>);
+
 fn get_auto_launch_builder() -> AutoLaunch {
     return AutoLaunchBuilder::new()
         .set_app_name("release-monitor")
@@ -66,12 +81,8 @@ fn set_auto_launch(auto_launch: bool) -> bool {
 #[tauri::command]
 fn set_naggy(app_handle: tauri::AppHandle, naggy: bool) {
     let config_path = get_config_path();
-    let mut version_checker_config = get_config(&config_path);
 
-    version_checker_config.naggy = naggy;
-
-    let toml_string = toml::to_string_pretty(&version_checker_config).unwrap();
-    match fs::write(&config_path.join("app.toml"), &toml_string){
+    match config::write_user_value(&config_path, "naggy", naggy) {
         Ok(_) => {}
         Err(e) => {
             error!("Failed to write config file! {}", e);
@@ -82,19 +93,22 @@ fn set_naggy(app_handle: tauri::AppHandle, naggy: bool) {
 }
 
 #[tauri::command]
-fn get_naggy() -> bool {
+fn get_naggy(org_config: State<Arc<OrgConfigCache>>) -> bool {
     let config_path = get_config_path();
-    let mut version_checker_config = get_config(&config_path);
+    let version_checker_config = get_config(&config_path, &org_config);
 
     version_checker_config.naggy
 }
 
+#[tauri::command]
+fn get_config_entries(org_config: State<Arc<OrgConfigCache>>) -> Vec<ConfigEntry> {
+    let config_path = get_config_path();
+    config::config_entries(&config::figment(&config_path, &org_config))
+}
 
-fn get_config(config_path: &Arc<PathBuf>) -> VersionCheckerConfig {
+fn get_config(config_path: &Arc<PathBuf>, org_config: &OrgConfigCache) -> VersionCheckerConfig {
     let mut version_checker_config = VersionCheckerConfig::default();
-    match Figment::from(VersionCheckerConfig::default())
-        .merge(Toml::file(&config_path.join("app.toml")))
-        .extract::<VersionCheckerConfig>() {
+    match config::figment(config_path, org_config).extract::<VersionCheckerConfig>() {
         Ok(c) => {
             version_checker_config = c;
         }
@@ -112,62 +126,271 @@ fn get_config_path() -> Arc<PathBuf> {
 }
 
 #[tauri::command]
-fn get_latest_version(services: tauri::State<HashMap<&str, Arc<dyn Any +Send + Sync>>>) -> String {
-    match services.get("version_checker") {
-        None => {}
-        Some(r) => {
-            let vc : Arc<SharedFolderVersionChecker> = r.clone().downcast::<SharedFolderVersionChecker>().unwrap();
-            let build_version = vc.get_latest_version().unwrap();
-            return build_version.to_string();
+fn get_latest_version(services: tauri::State<AppServices>) -> String {
+    services.latest_version()
+}
+
+#[tauri::command]
+fn get_latest_release(services: tauri::State<AppServices>) -> Option<DetectedRelease> {
+    services.latest_release()
+}
+
+#[tauri::command]
+fn open_latest_release(services: tauri::State<AppServices>) -> bool {
+    let result = match services.latest_release() {
+        Some(release) => release.open(),
+        None => return false
+    };
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Unable to open latest release! Error: {}", e);
+            false
         }
     }
+}
 
-    return String::from("");
+#[tauri::command]
+fn reveal_latest_release(services: tauri::State<AppServices>) -> bool {
+    let result = match services.latest_release() {
+        Some(release) => release.reveal(),
+        None => return false
+    };
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Unable to reveal latest release! Error: {}", e);
+            false
+        }
+    }
+}
+
+#[tauri::command]
+fn get_acked(services: tauri::State<AppServices>, version : String) -> bool {
+    services.is_acked(&version)
+}
+
+#[tauri::command]
+fn acknowledge(services: tauri::State<AppServices>, version:String, note: Option<String>) -> bool {
+    services.acknowledge(&version, note)
 }
 
 #[tauri::command]
-fn get_acked(services: tauri::State<HashMap<&str, Arc<dyn Any +Send + Sync>>>, version : String) -> bool {
-    let v = BuildVersion::parse(version.as_str()).unwrap();
+fn undo_acknowledge(services: tauri::State<AppServices>) -> String {
+    services.undo_acknowledge()
+}
+
+#[tauri::command]
+fn get_ack_history(services: tauri::State<AppServices>) -> Vec<AckRecord> {
+    services.ack_history()
+}
+
+#[tauri::command]
+fn get_release_history(services: tauri::State<AppServices>) -> Vec<ReleaseEntry> {
+    services.release_history()
+}
 
-    if v == BuildVersion::default() {
-        return true;
+#[tauri::command]
+fn get_release_statistics(services: tauri::State<AppServices>) -> ReleaseStatistics {
+    services.release_statistics()
+}
+
+#[tauri::command]
+fn get_source_health(services: tauri::State<AppServices>) -> SourceHealth {
+    services.source_health()
+}
+
+#[tauri::command]
+fn get_event_metrics(services: tauri::State<AppServices>) -> Vec<SubscriberMetrics> {
+    services.event_metrics()
+}
+
+/// Carries over the acknowledgement from the old `version.txt` in the temp dir, if there is one.
+fn import_legacy_ack(version_updater: &dyn VersionUpdater) {
+    let legacy_file = env::temp_dir().join(r"version.txt");
+    if version_updater.get_version() != BuildVersion::default() || !metadata(&legacy_file).is_ok() {
+        return;
     }
 
-    match services.get("version_updater") {
-        None => {}
-        Some(r) => {
-            let vu : Arc<FileCacheVersionUpdater > = r.clone().downcast::<FileCacheVersionUpdater >().unwrap();
-            let ack_version = vu.get_version();
-            return ack_version == v;
+    match fs::read_to_string(&legacy_file) {
+        Ok(s) => {
+            match BuildVersion::parse(&s) {
+                Ok(v) if v != BuildVersion::default() => {
+                    info!("Imported acknowledged version {} from {}.", v, legacy_file.display());
+                    version_updater.set_version(v, Some(String::from("Imported from version.txt")));
+                }
+                _ => {}
+            }
         }
+        Err(e) => {
+            error!("Unable to read {}. Error: {}", legacy_file.display(), e);
+        }
+    }
+}
+
+/// The daemon to defer to, unless this process is the daemon.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn get_daemon_socket(config: &VersionCheckerConfig, command: &Option<cli::Command>) -> Option<String> {
+    #[cfg(unix)]
+    if let Some(cli::Command::Daemon { .. }) = command {
+        return None;
+    }
+    config.daemon_socket.clone()
+}
+
+/// Scans the configured share, or defers to a running daemon when `daemon_socket` is set.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn get_sources(config: &VersionCheckerConfig, daemon_socket: &Option<String>, state_store: &Arc<SqliteStateStore>)
+    -> (Arc<dyn VersionChecker + Send + Sync>, Arc<dyn VersionUpdater + Send + Sync>) {
+    #[cfg(unix)]
+    if let Some(socket) = daemon_socket {
+        info!("Using daemon at {}.", socket);
+        let client = Arc::new(DaemonClient::new(&PathBuf::from(socket)));
+        return (client.clone(), client);
     }
 
-    return false;
+    (Arc::new(SharedFolderVersionChecker::new(config.path.as_str(), config.file_regex.as_str())), state_store.clone())
 }
 
-#[tauri::command]
-fn acknowledge(app_handle: tauri::AppHandle, services: tauri::State<HashMap<&str, Arc<dyn Any +Send + Sync>>>, version:String) -> bool {
-    let v = BuildVersion::parse(version.as_str()).unwrap();
-    if v == BuildVersion::default() {
-        return false;
+/// Passes on what the daemon publishes when `daemon_socket` is set, in place of scanning here. The
+/// daemon runs the notifiers, so they aren't attached locally either.
+#[cfg(unix)]
+fn follow_daemon(daemon_socket: &Option<String>, release_monitor: &Arc<ReleaseMonitor>) -> bool {
+    let socket = match daemon_socket {
+        Some(s) => s,
+        None => return false
+    };
+    let monitor = Arc::downgrade(release_monitor);
+    DaemonClient::new(&PathBuf::from(socket)).subscribe(move |m| {
+        if let Some(monitor) = monitor.upgrade() {
+            monitor.publish(m);
+        }
+    });
+    true
+}
+
+#[cfg(not(unix))]
+fn follow_daemon(_daemon_socket: &Option<String>, _release_monitor: &Arc<ReleaseMonitor>) -> bool {
+    false
+}
+
+/// Starts the HTTP API when `http_port` is configured.
+fn start_http_api(config: &VersionCheckerConfig, services: &AppServices) -> Option<HttpApi> {
+    let port = config.http_port?;
+    match HttpApi::start(port, config.http_token.as_deref().unwrap_or_default(), services.clone()) {
+        Ok(api) => Some(api),
+        Err(e) => {
+            error!("Unable to start HTTP API! Error: {}", e);
+            None
+        }
+    }
+}
+
+/// Shows a desktop notification. Freedesktop notification servers support buttons, so the actions
+/// are offered there and the picked one is handed to `on_action`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn show_notification<F>(_app: &tauri::AppHandle, text: NotificationText, on_action: F) -> Result<(), anyhow::Error>
+    where F: FnOnce(NotificationAction) + Send + 'static {
+    let mut notification = notify_rust::Notification::new();
+    notification.appname("Release Monitor").summary(&text.title).body(&text.body);
+    for (action, label) in &text.actions {
+        notification.action(action.id(), label);
     }
+    let handle = notification.show()?;
+    thread::spawn(move || {
+        handle.wait_for_action(|id| {
+            if let Some(action) = NotificationAction::from_id(id) {
+                on_action(action);
+            }
+        });
+    });
+    Ok(())
+}
+
+/// Shows a desktop notification. Tauri's notifications have no buttons on this platform, so the
+/// actions stay in the window and the tray.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn show_notification<F>(app: &tauri::AppHandle, text: NotificationText, _on_action: F) -> Result<(), anyhow::Error>
+    where F: FnOnce(NotificationAction) + Send + 'static {
+    Notification::new(&app.config().tauri.bundle.identifier)
+        .title(&text.title)
+        .body(&text.body)
+        .show()?;
+    Ok(())
+}
 
-    match services.get("release_monitor") {
-        None => {}
-        Some(r) => {
-            let vc : Arc<ReleaseMonitor> = r.clone().downcast::<ReleaseMonitor>().unwrap();
-            vc.acknowledge(v);
-            app_handle.tray_handle().set_icon(tauri::Icon::Raw(include_bytes!("../icons/icon.ico").to_vec())).unwrap();
-            return true;
+/// Sends `payload` to the main window. Only logs on failure; this runs in event listeners.
+fn emit_to_main<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    match app.get_window("main") {
+        Some(window) => match window.emit(event, payload) {
+            Ok(_) => {}
+            Err(e) => {
+                error!("Unable to emit {}! Error: {}", event, e);
+            }
+        },
+        None => {
+            error!("Unable to emit {}! The main window is gone.", event);
         }
     }
+}
 
-    return false;
+fn set_tray_icon(app: &tauri::AppHandle, icon: &[u8]) {
+    match app.tray_handle().set_icon(tauri::Icon::Raw(icon.to_vec())) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Unable to update tray icon! Error: {}", e);
+        }
+    }
 }
 
+/// Builds the notifiers which are told about new versions. Chats record what they posted in the
+/// watched folder unless configured to use another shared folder.
+fn get_notifier_hub(config: &VersionCheckerConfig, release_monitor: &Arc<ReleaseMonitor>) -> NotifierHub {
+    let mut hub = NotifierHub::default();
+    for webhook in &config.webhooks {
+        hub.add(Arc::new(WebhookNotifier::new(webhook.clone())));
+    }
+    for chat in &config.chats {
+        hub.add(Arc::new(ChatNotifier::new(chat.clone()).with_dedup_dir(&Path::new(&config.path).join(DEDUP_DIR))));
+    }
+    for email in &config.emails {
+        hub.add(Arc::new(EmailNotifier::new(email.clone())));
+    }
+    for command in &config.commands {
+        hub.add(Arc::new(CommandNotifier::new(command.clone())));
+    }
+    if let Some(download) = &config.download {
+        let monitor = Arc::downgrade(release_monitor);
+        let downloader = ArtifactDownloader::new(download.clone()).with_progress(move |p| {
+            if let Some(monitor) = monitor.upgrade() {
+                monitor.publish(Message::DownloadProgress(p));
+            }
+        });
+        hub.add(Arc::new(downloader));
+    }
+    hub
+}
+
+/// Release builds use the windows subsystem, so CLI output has to go to the console of the shell that started us.
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
 fn main() {
+    if std::env::args_os().len() > 1 {
+        attach_console();
+    }
+    let cli = Cli::parse();
     let config_path = get_config_path();
-    let mut version_checker_config = get_config(&config_path);
+    let org_config = Arc::new(OrgConfigCache::default());
+    let version_checker_config = get_config(&config_path, &org_config);
 
     let logfile = RollingFileAppender::builder()
         .rotation(Rotation::DAILY)
@@ -181,40 +404,100 @@ fn main() {
         .with_writer(logfile)
         .init();
 
-    let toml_string = toml::to_string_pretty(&version_checker_config).unwrap();
-    match fs::write(&config_path.join("app.toml"), &toml_string){
+    // Only seed app.toml when it doesn't exist yet, so org-provided values aren't frozen into the user's overrides.
+    let config_file = config_path.join(APP_CONFIG_FILE);
+    if !metadata(&config_file).is_ok() {
+        let toml_string = if OrgConfig::load(&OrgConfig::system_config_file()).base_config.is_some() {
+            String::from("# Local overrides for the org-provided config.\n")
+        } else {
+            toml::to_string_pretty(&version_checker_config).unwrap()
+        };
+        match fs::write(&config_file, &toml_string){
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to write config file! {}", e);
+            }
+        }
+    }
+
+    let project_dirs = ProjectDirs::from("com", "decryptology", "releasemonitor").unwrap();
+    let file_version_updater =
+        FileCacheVersionUpdater::new(&project_dirs.data_dir().join("acks.json"), version_checker_config.source.as_str());
+    import_legacy_ack(&file_version_updater);
+    let state_store = match SqliteStateStore::open(&project_dirs.data_dir().join("state.db"), version_checker_config.source.as_str()) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            error!("Unable to open state store! Error: {}", e);
+            eprintln!("Unable to open state store! Error: {}", e);
+            std::process::exit(cli::EXIT_ERROR);
+        }
+    };
+    match state_store.import_history(&file_version_updater.history()) {
         Ok(_) => {}
         Err(e) => {
-            error!("Failed to write config file! {}", e);
+            error!("Failed to import acknowledgement history! {}", e);
         }
     }
 
-    let version_checker =
-        Arc::new(SharedFolderVersionChecker::new( version_checker_config.path.as_str(), version_checker_config.file_regex.as_str()));
-    let version_updater =
-        Arc::new(FileCacheVersionUpdater::new(env::temp_dir().join(r"version.txt").to_str().unwrap()));
-
-    let release_monitor = Arc::new(ReleaseMonitor::new(version_checker.clone(), version_updater.clone(), version_checker_config.interval_seconds));
-    match release_monitor.start() {
-        Ok(_) => { info!("Release monitor started!")}
-        Err(_) => { error!("Failed to start monitor!") }
+    let daemon_socket = get_daemon_socket(&version_checker_config, &cli.command);
+    let (version_checker, version_updater) = get_sources(&version_checker_config, &daemon_socket, &state_store);
+    let mut release_monitor = ReleaseMonitor::new(version_checker_config.source.as_str(), version_checker.clone(), version_updater.clone(), state_store.clone(), version_checker_config.interval_seconds);
+    if cfg!(unix) && daemon_socket.is_some() {
+        release_monitor = release_monitor.following();
+    }
+    let release_monitor = Arc::new(release_monitor);
+    let services = AppServices {
+        release_monitor: release_monitor.clone(),
+        version_checker: version_checker.clone(),
+        version_updater: version_updater.clone()
+    };
+
+    let gate = Arc::new(NotificationGate::new(&version_checker_config.notification).with_store(state_store.clone()));
+
+    if let Some(command) = cli.command {
+        let (_http_api, _notifications) = match command {
+            cli::Command::Check | cli::Command::Ack { .. } | cli::Command::Reset => (None, Vec::new()),
+            _ if daemon_socket.is_some() => (start_http_api(&version_checker_config, &services), Vec::new()),
+            _ => (start_http_api(&version_checker_config, &services), get_notifier_hub(&version_checker_config, &release_monitor).with_gate(gate.clone()).attach(&release_monitor))
+        };
+        std::process::exit(cli::run(command, &services, &mut std::io::stdout()));
     }
 
-    let mut services : HashMap<&str, Arc<dyn Any +Send + Sync>> = HashMap::new();
-    services.insert("release_monitor", release_monitor.clone());
-    services.insert("version_checker", version_checker.clone());
-    services.insert("version_updater", version_updater.clone());
+    let _http_api = start_http_api(&version_checker_config, &services);
+    let notification_services = services.clone();
+
+    let _notifications = if follow_daemon(&daemon_socket, &release_monitor) {
+        info!("Following the daemon instead of scanning.");
+        Vec::new()
+    } else {
+        let notifications = get_notifier_hub(&version_checker_config, &release_monitor).with_gate(gate.clone()).attach(&release_monitor);
+        match release_monitor.start() {
+            Ok(_) => { info!("Release monitor started!")}
+            Err(_) => { error!("Failed to start monitor!") }
+        }
+        notifications
+    };
 
+    let health = CustomMenuItem::new("health".to_string(), "Source OK").disabled();
     let show = CustomMenuItem::new("show".to_string(), "Show");
     let edit_config = CustomMenuItem::new("edit_config".to_string(), "Edit Config");
-    let reset = CustomMenuItem::new("reset".to_string(), "Reset");
+    let undo = CustomMenuItem::new("undo".to_string(), "Undo Acknowledge");
+    let mut do_not_disturb = CustomMenuItem::new("do_not_disturb".to_string(), "Do Not Disturb");
+    if gate.do_not_disturb() {
+        do_not_disturb = do_not_disturb.selected();
+    }
+    let reload_config = CustomMenuItem::new("reload_config".to_string(), "Reload Config");
     let logs = CustomMenuItem::new("logs".to_string(), "Logs");
     let restart = CustomMenuItem::new("restart".to_string(), "Restart");
     let quit = CustomMenuItem::new("quit".to_string(), "Quit");
     let tray_menu = SystemTrayMenu::new()
+        .add_item(health)
+        .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(show)
         .add_item(edit_config)
-        .add_item(reset)
+        .add_item(reload_config)
+        .add_item(undo)
+        .add_item(do_not_disturb)
         .add_item(logs)
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(restart)
@@ -239,7 +522,7 @@ fn main() {
                         window.show().unwrap();
                     }
                     "edit_config" => {
-                        let config_file = c.join("app.toml");
+                        let config_file = c.join(APP_CONFIG_FILE);
 
                         if !metadata(&config_file).is_ok() {
                             OpenOptions::new().create(true).write(true).open(&config_file).unwrap();
@@ -251,13 +534,31 @@ fn main() {
                             }
                         }
                     }
-                    "reset" => {
-                        let services : State<HashMap<&str, Arc<dyn Any +Send + Sync>>> = app.state();
-                        match services.get("version_updater") {
-                            None => {}
-                            Some(r) => {
-                                let vc : Arc<FileCacheVersionUpdater> = r.clone().downcast::<FileCacheVersionUpdater>().unwrap();
-                                vc.reset();
+                    "reload_config" => {
+                        let services : State<AppServices> = app.state();
+                        let org_config : State<Arc<OrgConfigCache>> = app.state();
+                        org_config.refresh();
+                        let config = get_config(&c, &org_config);
+                        let gate : State<Arc<NotificationGate>> = app.state();
+                        gate.configure(&config.notification);
+                        services.release_monitor.set_interval(config.interval_seconds);
+                        services.release_monitor.publish(Message::ConfigReloaded(ConfigReloaded {
+                            interval_seconds: config.interval_seconds,
+                            naggy: config.naggy
+                        }));
+                    }
+                    "undo" => {
+                        let services : State<AppServices> = app.state();
+                        services.undo_acknowledge();
+                    }
+                    "do_not_disturb" => {
+                        let gate : State<Arc<NotificationGate>> = app.state();
+                        let on = !gate.do_not_disturb();
+                        gate.set_do_not_disturb(on);
+                        match app.tray_handle().get_item("do_not_disturb").set_selected(on) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                error!("Unable to update tray do not disturb! Error: {}", e);
                             }
                         }
                     }
@@ -291,7 +592,9 @@ fn main() {
             _ => {}
         })
         .manage(services)
-        .invoke_handler(tauri::generate_handler![get_latest_version, acknowledge, get_auto_launch, set_auto_launch, get_acked, set_naggy, get_naggy])
+        .manage(gate.clone())
+        .manage(org_config)
+        .invoke_handler(tauri::generate_handler![get_latest_version, acknowledge, get_auto_launch, set_auto_launch, get_acked, set_naggy, get_naggy, get_config_entries, undo_acknowledge, get_ack_history, get_release_history, get_release_statistics, get_event_metrics, get_source_health, get_latest_release, open_latest_release, reveal_latest_release])
         .setup(move |app| {
 
             let app = Arc::new(app.handle());
@@ -307,36 +610,94 @@ fn main() {
                     }
                 }
 
-                let main_window = app_one.get_window("main").unwrap();
-                main_window.emit("latest-version", title).unwrap();
+                emit_to_main(&app_one, "latest-version", title);
             });
 
             let app_two = app.clone();
-            let last_notified_version = Arc::new(Mutex::new(BuildVersion::default()));
-            let subscription = Arc::new(Subscription::new(Box::new(move |v| {
-                //println!("{}", v);
-                let main_window = app_two.get_window("main").unwrap();
-                main_window.emit("latest-version", v.to_string()).unwrap();
-
-                if !v.notify {
-                    return;
+            let naggy = Arc::new(AtomicBool::new(version_checker_config.naggy));
+            let naggy_one = naggy.clone();
+            let health_services = notification_services.clone();
+            let app_notify = app.clone();
+            let desktop = DesktopNotifier::new(notification_services.clone(), gate.clone(), version_checker_config.notification.clone(), Arc::new(move |text: NotificationText, on_action: Box<dyn FnOnce(NotificationAction) + Send>| {
+                match show_notification(&app_notify, text, on_action) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Unable to show notification! Error: {}", e);
+                    }
                 }
+            }));
+            let new_version = release_monitor.subscribe_many(&[Event::VersionDetected, Event::VersionReminder], move |m| {
+                let (release, reminder, notify) = match m {
+                    Message::VersionDetected(v) => (v.release.clone(), None, true),
+                    Message::VersionReminder(r) => {
+                        let release = notification_services.latest_release()
+                            .filter(|l| l.version == r.version)
+                            .unwrap_or(DetectedRelease::new(&r.source, r.version));
+                        (release, Some(r.count), naggy_one.load(Ordering::Relaxed))
+                    }
+                    _ => return
+                };
 
-                app_two.tray_handle().set_icon(tauri::Icon::Raw(include_bytes!("../icons/icon-blue.ico").to_vec())).unwrap();
-                if version_checker_config.naggy || *last_notified_version.lock().unwrap() != v.version {
-                    match Notification::new(&app_two.config().tauri.bundle.identifier)
-                        .title("Aiyoyo! Got new build version!")
-                        .body(format!("Mai tu liao! Must install {} right now!", v.to_string().as_str()))
-                        .show() {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("Unable to show notification! Error: {}", e);
-                        }
+                emit_to_main(&app_two, "latest-version", release.version.to_string());
+                set_tray_icon(&app_two, include_bytes!("../icons/icon-blue.ico"));
+                if notify {
+                    desktop.notify(release, reminder);
+                }
+            });
+
+            let app_three = app.clone();
+            let acknowledged = release_monitor.subscribe(Event::VersionAcknowledged, move |_| {
+                set_tray_icon(&app_three, include_bytes!("../icons/icon.ico"));
+            });
+
+            let config_reloaded = release_monitor.subscribe(Event::ConfigReloaded, move |m| {
+                if let Message::ConfigReloaded(c) = m {
+                    naggy.store(c.naggy, Ordering::Relaxed);
+                }
+            });
+
+            let app_five = app.clone();
+            let health_changed = release_monitor.subscribe(Event::HealthChanged, move |m| {
+                let h = match m {
+                    Message::HealthChanged(h) => h,
+                    _ => return
+                };
+
+                let title = match h.status {
+                    HealthStatus::Ok => String::from("Source OK"),
+                    HealthStatus::Degraded => format!("Source degraded: {}", h.last_error.clone().unwrap_or_default()),
+                    HealthStatus::Unreachable => format!("Source unreachable: {}", h.last_error.clone().unwrap_or_default())
+                };
+                match h.status {
+                    HealthStatus::Ok if health_services.is_acked(&health_services.release_monitor.last_seen().to_string()) => {
+                        set_tray_icon(&app_five, include_bytes!("../icons/icon.ico"));
                     }
-                    *last_notified_version.lock().unwrap() = v.version;
+                    HealthStatus::Ok => set_tray_icon(&app_five, include_bytes!("../icons/icon-blue.ico")),
+                    _ => set_tray_icon(&app_five, include_bytes!("../icons/icon-degraded.ico"))
                 }
-            })));
-            release_monitor.subscribe(Event::NewVersion, subscription.clone());
+                let tray = app_five.tray_handle();
+                match tray.get_item("health").set_title(&title) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Unable to update tray health! Error: {}", e);
+                    }
+                }
+                match tray.set_tooltip(&title) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Unable to update tray tooltip! Error: {}", e);
+                    }
+                }
+            });
+
+            // Forward everything to the UI and the log.
+            let app_four = app.clone();
+            let forward = release_monitor.subscribe_many(&Event::ALL, move |m| {
+                info!("Event: {:?}", m);
+                emit_to_main(&app_four, "monitor-event", m);
+            });
+
+            app.manage(Subscriptions(vec![new_version, acknowledged, config_reloaded, health_changed, forward]));
 
             Ok(())
         })
@@ -0,0 +1,61 @@
+import React, {useEffect, useState} from "react";
+import { invoke } from '@tauri-apps/api/tauri'
+
+interface AckRecord {
+    action: "acknowledged" | "undone";
+    version: string;
+    source: string;
+    timestamp: string;
+    user?: string;
+    note?: string;
+}
+
+const AckHistoryPane = React.memo(() =>{
+    const [records, setRecords] = useState<AckRecord[]>([]);
+
+    function refresh(){
+        invoke<AckRecord[]>('get_ack_history').then((r) => setRecords(r));
+    }
+
+    useEffect(()=>{
+        refresh();
+    }, []);
+
+    function handleUndo(){
+        invoke('undo_acknowledge').then(() => refresh());
+    }
+
+    return <details className="w-full text-sm text-gray-700 dark:text-gray-300" onToggle={refresh}>
+        <summary className="cursor-pointer font-medium">Who acknowledge what ah?</summary>
+        <button type="button"
+                className="mt-2 text-blue-600 underline dark:text-blue-500 hover:no-underline disabled:text-slate-500"
+                disabled={records.length === 0}
+                onClick={handleUndo}>Undo last acknowledge</button>
+        <table className="w-full mt-2 text-left">
+            <thead>
+            <tr className="border-b border-gray-200 dark:border-gray-700">
+                <th className="py-1">When</th>
+                <th className="py-1">What</th>
+                <th className="py-1">Version</th>
+                <th className="py-1">Who</th>
+                <th className="py-1">Note</th>
+            </tr>
+            </thead>
+            <tbody>
+            {records.map((r, i) =>
+                <tr key={i} className="border-b border-gray-100 dark:border-gray-800">
+                    <td className="py-1">{new Date(r.timestamp).toLocaleString()}</td>
+                    <td className="py-1">{r.action}</td>
+                    <td className="py-1 font-mono">{r.version}</td>
+                    <td className="py-1">{r.user ?? ""}</td>
+                    <td className="py-1">{r.note ?? ""}</td>
+                </tr>
+            )}
+            </tbody>
+        </table>
+    </details>;
+});
+
+AckHistoryPane.displayName = "AckHistoryPane";
+
+export default AckHistoryPane;
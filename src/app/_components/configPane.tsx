@@ -0,0 +1,43 @@
+import React, {useEffect, useState} from "react";
+import { invoke } from '@tauri-apps/api/tauri'
+
+interface ConfigEntry {
+    key: string;
+    value: string;
+    origin: string;
+    source?: string;
+}
+
+const ConfigPane = React.memo(() =>{
+    const [entries, setEntries] = useState<ConfigEntry[]>([]);
+
+    useEffect(()=>{
+        invoke<ConfigEntry[]>('get_config_entries').then((e) => setEntries(e));
+    }, []);
+
+    return <details className="w-full text-sm text-gray-700 dark:text-gray-300">
+        <summary className="cursor-pointer font-medium">Where my config come from ah?</summary>
+        <table className="w-full mt-2 text-left">
+            <thead>
+            <tr className="border-b border-gray-200 dark:border-gray-700">
+                <th className="py-1">Key</th>
+                <th className="py-1">Value</th>
+                <th className="py-1">From</th>
+            </tr>
+            </thead>
+            <tbody>
+            {entries.map((e) =>
+                <tr key={e.key} className="border-b border-gray-100 dark:border-gray-800">
+                    <td className="py-1 font-mono">{e.key}</td>
+                    <td className="py-1 font-mono break-all">{e.value}</td>
+                    <td className="py-1" title={e.source ?? ""}>{e.origin}</td>
+                </tr>
+            )}
+            </tbody>
+        </table>
+    </details>;
+});
+
+ConfigPane.displayName = "ConfigPane";
+
+export default ConfigPane;
@@ -7,22 +7,42 @@ const VersionPane = React.memo((props , context) =>{
     const [ack, setAck] = useState(true);
     const [autoLaunch, setAutoLaunch] = useState(false);
     const [naggy, setNaggy] = useState(false);
+    const [health, setHealth] = useState<{status: string, last_error: string | null} | null>(null);
+    const [release, setRelease] = useState<{path: string | null, raw_name: string | null, size: number | null} | null>(null);
+    const [download, setDownload] = useState<{version: string, copied: number, total: number, done: boolean} | null>(null);
 
     useEffect(()=>{
         invoke('get_latest_version').then((v: any) => setVersion(v));
         invoke('get_auto_launch').then((b: any) => setAutoLaunch(b));
         invoke('get_acked', {version: version}).then((a: any) => setAck(a));
         invoke('get_naggy').then((b: any) => setNaggy(b));
+        invoke('get_source_health').then((h: any) => setHealth(h));
+        invoke('get_latest_release').then((r: any) => setRelease(r));
 
         const unListen = listen<string>('latest-version', (event) => {
             console.log('Received event:', event.payload);
             setVersion(event.payload);
         });
 
+        const unListenEvents = listen<{kind: string, payload: any}>('monitor-event', (event) => {
+            if (event.payload.kind === 'version_detected') {
+                setRelease(event.payload.payload.release);
+            } else if (event.payload.kind === 'version_acknowledged') {
+                setAck(true);
+            } else if (event.payload.kind === 'ack_reset') {
+                setAck(false);
+            } else if (event.payload.kind === 'health_changed') {
+                setHealth(event.payload.payload);
+            } else if (event.payload.kind === 'download_progress') {
+                setDownload(event.payload.payload);
+            }
+        });
+
         return () => {
             if(unListen){
                 unListen.then(f => f());
             }
+            unListenEvents.then(f => f());
         };
     });
 
@@ -33,6 +53,14 @@ const VersionPane = React.memo((props , context) =>{
         });
     }
 
+    function handleOpen(){
+        invoke('open_latest_release').then();
+    }
+
+    function handleReveal(){
+        invoke('reveal_latest_release').then();
+    }
+
     function handleChangeAutoLaunch(e : React.ChangeEvent<HTMLInputElement>)    {
         console.log(e.target.checked);
         invoke('set_auto_launch', {autoLaunch: e.target.checked})
@@ -50,6 +78,22 @@ const VersionPane = React.memo((props , context) =>{
             className="flex flex-col items-center justify-center p-6 bg-white border border-gray-200 rounded-lg shadow dark:bg-gray-800 dark:border-gray-700">
             <div>The latest build version is</div>
             <h1 className="text-[80px] font-extrabold">{version}</h1>
+            {health && health.status !== 'ok' &&
+                <div className="mb-2 text-sm text-red-600 dark:text-red-400">
+                    Alamak, cannot check the builds ({health.status}): {health.last_error}
+                </div>}
+            {release && release.path &&
+                <div className="mb-2 text-sm">
+                    {release.raw_name}{release.size !== null && ` (${(release.size / 1048576).toFixed(1)} MB)`}
+                    <button type="button" className="ms-2 text-blue-700 hover:underline dark:text-blue-400" onClick={handleOpen}>Open file</button>
+                    <button type="button" className="ms-2 text-blue-700 hover:underline dark:text-blue-400" onClick={handleReveal}>Show in folder</button>
+                </div>}
+            {download &&
+                <div className="mb-2 text-sm">
+                    {download.done
+                        ? `Copied ${download.version} to your PC already.`
+                        : `Copying ${download.version} to your PC... ${Math.floor(download.copied * 100 / Math.max(download.total, 1))}%`}
+                </div>}
             <button type="button"
                     className="disabled:bg-slate-200 disabled:text-slate-500 disabled:hover:bg-slate-50 text-white bg-blue-700 hover:bg-blue-800 focus:ring-4 focus:ring-blue-300 font-medium rounded-lg text-sm px-5 py-2.5 me-2 mb-2 dark:bg-blue-600 dark:hover:bg-blue-700 focus:outline-none dark:focus:ring-blue-800"
                     onClick={handleAcknowledge}
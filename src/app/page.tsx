@@ -1,11 +1,17 @@
 'use client'
 import Image from "next/image";
+import Link from "next/link";
 import VersionPane from "@/app/_components/versionPane";
+import ConfigPane from "@/app/_components/configPane";
+import AckHistoryPane from "@/app/_components/ackHistoryPane";
 
 export default function Home() {
   return (
       <main className="flex min-h-screen flex-col items-center justify-between p-10">
           <VersionPane/>
+          <AckHistoryPane/>
+          <ConfigPane/>
+          <Link className="font-medium text-blue-600 underline dark:text-blue-500 hover:no-underline" href="/statistics">Statistics</Link>
           <div className="absolute bottom-10 left-0 right-0 text-center text-gray-700 dark:text-white">
               Made with <a className="font-medium text-blue-600 underline dark:text-blue-500 hover:no-underline"
                            href="https://tauri.app/" target="_blank">Tauri</a> and <a
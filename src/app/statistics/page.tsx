@@ -0,0 +1,98 @@
+'use client'
+import React, {useEffect, useState} from "react";
+import Link from "next/link";
+import { invoke } from '@tauri-apps/api/tauri'
+
+interface ReleaseEntry {
+    version: string;
+    first_seen: string;
+    acknowledged_at?: string;
+    time_to_acknowledge_seconds?: number;
+}
+
+interface ReleaseStatistics {
+    source: string;
+    total_builds: number;
+    builds_per_day: { date: string, builds: number }[];
+    average_builds_per_day: number;
+    median_time_to_acknowledge_seconds?: number;
+    median_gap_seconds?: number;
+    longest_gap_seconds?: number;
+    seconds_since_last_build?: number;
+}
+
+function formatDuration(seconds?: number) {
+    if (seconds === undefined || seconds === null) {
+        return "-";
+    }
+    const days = Math.floor(seconds / 86400);
+    const hours = Math.floor((seconds % 86400) / 3600);
+    const minutes = Math.floor((seconds % 3600) / 60);
+    if (days > 0) {
+        return `${days}d ${hours}h`;
+    }
+    return hours > 0 ? `${hours}h ${minutes}m` : `${minutes}m`;
+}
+
+export default function Statistics() {
+    const [history, setHistory] = useState<ReleaseEntry[]>([]);
+    const [stats, setStats] = useState<ReleaseStatistics | null>(null);
+
+    useEffect(()=>{
+        invoke<ReleaseEntry[]>('get_release_history').then((h) => setHistory(h));
+        invoke<ReleaseStatistics | null>('get_release_statistics').then((s) => setStats(s));
+    }, []);
+
+    const maxBuilds = Math.max(1, ...(stats?.builds_per_day.map((d) => d.builds) ?? []));
+
+    return (
+        <main className="flex min-h-screen flex-col space-y-4 p-10 text-gray-700 dark:text-white">
+            <div className="flex justify-between">
+                <h1 className="text-2xl font-extrabold">How often got new build ah? ({stats?.source})</h1>
+                <Link className="font-medium text-blue-600 underline dark:text-blue-500 hover:no-underline" href="/">Back</Link>
+            </div>
+            <div className="grid grid-cols-4 gap-3 text-center">
+                {[
+                    ["Builds", `${stats?.total_builds ?? 0}`],
+                    ["Builds per day", (stats?.average_builds_per_day ?? 0).toFixed(2)],
+                    ["Median time to acknowledge", formatDuration(stats?.median_time_to_acknowledge_seconds)],
+                    ["Since last build", formatDuration(stats?.seconds_since_last_build)],
+                    ["Median gap", formatDuration(stats?.median_gap_seconds)],
+                    ["Longest gap", formatDuration(stats?.longest_gap_seconds)],
+                ].map(([label, value]) =>
+                    <div key={label} className="p-3 bg-white border border-gray-200 rounded-lg shadow dark:bg-gray-800 dark:border-gray-700">
+                        <div className="text-sm">{label}</div>
+                        <div className="text-xl font-bold">{value}</div>
+                    </div>
+                )}
+            </div>
+            <div className="flex items-end h-24 space-x-1">
+                {stats?.builds_per_day.map((d) =>
+                    <div key={d.date} title={`${d.date}: ${d.builds}`}
+                         className="w-3 bg-blue-600 dark:bg-blue-500"
+                         style={{height: `${(d.builds / maxBuilds) * 100}%`}}/>
+                )}
+            </div>
+            <table className="w-full text-sm text-left">
+                <thead>
+                <tr className="border-b border-gray-200 dark:border-gray-700">
+                    <th className="py-1">Version</th>
+                    <th className="py-1">First seen</th>
+                    <th className="py-1">Acknowledged</th>
+                    <th className="py-1">Took</th>
+                </tr>
+                </thead>
+                <tbody>
+                {[...history].reverse().map((e) =>
+                    <tr key={e.version + e.first_seen} className="border-b border-gray-100 dark:border-gray-800">
+                        <td className="py-1 font-mono">{e.version}</td>
+                        <td className="py-1">{new Date(e.first_seen).toLocaleString()}</td>
+                        <td className="py-1">{e.acknowledged_at ? new Date(e.acknowledged_at).toLocaleString() : "-"}</td>
+                        <td className="py-1">{formatDuration(e.time_to_acknowledge_seconds)}</td>
+                    </tr>
+                )}
+                </tbody>
+            </table>
+        </main>
+    );
+}